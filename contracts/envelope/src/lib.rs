@@ -1,10 +1,12 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, panic_with_error, symbol, Address, Env, Symbol,
+    contract, contracterror, contractimpl, contracttype, panic_with_error, Address, Env, Map,
+    Symbol, Vec,
 };
 mod reflector;
-use reflector::{last_usd, usd_at, FxPrice};
+use reflector::{guarded_usd, last_usd, twap_usd, MAX_PRICE_AGE, TWAP_SAMPLES};
+pub use reflector::{FxPrice, OracleMode};
 
 #[contracttype]
 #[derive(Clone)]
@@ -12,8 +14,21 @@ pub enum DataKey {
     NextId,
     Envelope(u64),
     ReflectorFx,
+    Admin,
+    MaxDeviationBps,
+    Oracles,
+    OracleMode,
+    CreatorCount(Address),
+    CreatorAt(Address, u32),
+    RecipientCount(Address),
+    RecipientAt(Address, u32),
+    Stats,
+    Locked(Address),
 }
 
+/// Upper bound on `limit` for the paginated list views.
+pub const MAX_PAGE: u32 = 50;
+
 #[derive(Clone)]
 #[contracttype]
 pub struct EnvelopeData {
@@ -26,6 +41,8 @@ pub struct EnvelopeData {
     pub denom: Symbol,
     pub opened: bool,
     pub expiry_ts: u64,
+    pub locked_fx: FxPrice,
+    pub twap_window: u64,
 }
 
 #[derive(Clone)]
@@ -47,7 +64,51 @@ pub struct EnvelopeOpened {
     pub ts: u64,
 }
 
-#[derive(Copy, Clone, Debug)]
+/// `current_usd` uses the latest reading whatever its age; `price_ts` is when it was taken.
+#[derive(Clone)]
+#[contracttype]
+pub struct EnvelopeValuation {
+    pub id: u64,
+    pub locked_usd: i128,
+    pub current_usd: i128,
+    pub delta: i128,
+    pub ts: u64,
+    pub price_ts: u64,
+}
+
+/// Running totals across all envelopes; `locked` is the outstanding amount per requested asset.
+#[derive(Clone)]
+#[contracttype]
+pub struct EnvelopeStats {
+    pub total: u64,
+    pub pending: u64,
+    pub opened: u64,
+    pub refunded: u64,
+    pub locked: Map<Address, i128>,
+}
+
+/// Counters kept in instance storage; per-asset totals live under `DataKey::Locked`
+/// so that arbitrary assets cannot grow the instance entry.
+#[derive(Clone)]
+#[contracttype]
+struct Counters {
+    total: u64,
+    pending: u64,
+    opened: u64,
+    refunded: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SolvencyReport {
+    pub asset: Address,
+    pub liabilities: i128,
+    pub balance: i128,
+    pub shortfall: i128,
+}
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 #[repr(u32)]
 pub enum Err {
     NotFound = 1,
@@ -56,29 +117,205 @@ pub enum Err {
     PriceStale = 4,
     NotRecipient = 5,
     Expired = 6,
+    PriceInvalid = 7,
+    NotAdmin = 8,
+    OracleUnavailable = 9,
+    WindowInvalid = 10,
+    AlreadyInitialized = 11,
 }
 
 fn now(env: &Env) -> u64 {
     env.ledger().timestamp()
 }
 
+fn require_admin(env: &Env, admin: &Address) {
+    admin.require_auth();
+    let stored: Address = env
+        .storage()
+        .instance()
+        .get(&DataKey::Admin)
+        .unwrap_or_else(|| panic_with_error!(env, Err::NotAdmin));
+    if stored != *admin {
+        panic_with_error!(env, Err::NotAdmin);
+    }
+}
+
+fn load(env: &Env, id: u64) -> EnvelopeData {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Envelope(id))
+        .unwrap_or_else(|| panic_with_error!(env, Err::NotFound))
+}
+
+/// Appends `id` to a per-address index stored as a count plus one slot per entry.
+fn index_push(env: &Env, count_key: DataKey, slot: impl Fn(u32) -> DataKey, id: u64) {
+    let n: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+    env.storage().persistent().set(&slot(n), &id);
+    env.storage().persistent().set(&count_key, &(n + 1));
+}
+
+fn index_page(
+    env: &Env,
+    count_key: DataKey,
+    slot: impl Fn(u32) -> DataKey,
+    cursor: u32,
+    limit: u32,
+) -> Vec<EnvelopeData> {
+    let n: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+    let end = cursor.saturating_add(limit.min(MAX_PAGE)).min(n);
+    let mut out = Vec::new(env);
+    for i in cursor..end {
+        let id: u64 = env.storage().persistent().get(&slot(i)).unwrap();
+        out.push_back(load(env, id));
+    }
+    out
+}
+
+fn load_counters(env: &Env) -> Counters {
+    env.storage()
+        .instance()
+        .get(&DataKey::Stats)
+        .unwrap_or(Counters {
+            total: 0,
+            pending: 0,
+            opened: 0,
+            refunded: 0,
+        })
+}
+
+fn locked(env: &Env, asset: &Address) -> i128 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Locked(asset.clone()))
+        .unwrap_or(0)
+}
+
+/// Applies `f` to the stored counters and moves `delta` into (or out of) the asset's locked total.
+fn update_stats(env: &Env, asset: &Address, delta: i128, f: impl FnOnce(&mut Counters)) {
+    let mut counters = load_counters(env);
+    f(&mut counters);
+    env.storage().instance().set(&DataKey::Stats, &counters);
+    env.storage()
+        .persistent()
+        .set(&DataKey::Locked(asset.clone()), &(locked(env, asset) + delta));
+}
+
 fn mul_div(a: i128, b: i128, scale: i128) -> i128 {
     let prod = a.checked_mul(b).expect("mul overflow");
     prod.checked_div(scale).expect("div overflow/zero")
 }
 
+#[allow(clippy::too_many_arguments)]
+fn create(
+    env: &Env,
+    creator: Address,
+    recipient: Address,
+    asset: Address,
+    amount_in: i128,
+    denom: Symbol,
+    expiry_secs: u64,
+    twap_window: u64,
+) -> u64 {
+    if amount_in <= 0 {
+        panic_with_error!(env, Err::AmountZero);
+    }
+    creator.require_auth();
+
+    let fx = guarded_usd(env, Some(MAX_PRICE_AGE));
+    let cur = now(env);
+    let locked_fx = if twap_window == 0 {
+        fx
+    } else {
+        twap_usd(env, twap_window, TWAP_SAMPLES)
+    };
+
+    TokenClient::new(env, &asset).transfer(&creator, &env.current_contract_address(), &amount_in);
+
+    let mut id = env.storage().instance().get::<DataKey, u64>(&DataKey::NextId).unwrap_or(0);
+    id += 1;
+    env.storage().instance().set(&DataKey::NextId, &id);
+
+    let created_ts = cur;
+    let expiry_ts = if expiry_secs == 0 { 0 } else { created_ts.saturating_add(expiry_secs) };
+
+    let data = EnvelopeData {
+        id,
+        creator: creator.clone(),
+        recipient: recipient.clone(),
+        asset: asset.clone(),
+        amount_in,
+        created_ts,
+        denom,
+        opened: false,
+        expiry_ts,
+        locked_fx,
+        twap_window,
+    };
+    env.storage().persistent().set(&DataKey::Envelope(id), &data);
+    index_push(
+        env,
+        DataKey::CreatorCount(creator.clone()),
+        |i| DataKey::CreatorAt(creator.clone(), i),
+        id,
+    );
+    index_push(
+        env,
+        DataKey::RecipientCount(recipient.clone()),
+        |i| DataKey::RecipientAt(recipient.clone(), i),
+        id,
+    );
+    update_stats(env, &asset, amount_in, |st| {
+        st.total += 1;
+        st.pending += 1;
+    });
+
+    env.events().publish(
+        (Symbol::new(env, "EnvelopeCreated"),),
+        EnvelopeCreated {
+            id,
+            creator,
+            recipient,
+            asset,
+            amount_in,
+            ts: created_ts,
+        },
+    );
+
+    id
+}
+
 #[contract]
 pub struct Envelope;
 
 #[contractimpl]
 impl Envelope {
-    pub fn init(env: Env, reflector_fx: Address) {
+    pub fn init(env: Env, admin: Address, reflector_fx: Address) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic_with_error!(&env, Err::AlreadyInitialized);
+        }
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage()
             .instance()
             .set(&Symbol::new(&env, "reflector_fx"), &reflector_fx);
         env.storage().instance().set(&DataKey::NextId, &0u64);
     }
 
+    /// Max allowed move from the oracle's reading one resolution period earlier, in basis
+    /// points (0 = off).
+    pub fn set_max_deviation(env: Env, admin: Address, bps: u32) {
+        require_admin(&env, &admin);
+        env.storage().instance().set(&DataKey::MaxDeviationBps, &bps);
+    }
+
+    /// Oracles queried in order; `Median` takes the middle of all valid readings.
+    /// An empty list falls back to the `reflector_fx` address given to `init`.
+    pub fn set_oracles(env: Env, admin: Address, oracles: Vec<Address>, mode: OracleMode) {
+        require_admin(&env, &admin);
+        env.storage().instance().set(&DataKey::Oracles, &oracles);
+        env.storage().instance().set(&DataKey::OracleMode, &mode);
+    }
+
     pub fn create_envelope(
         env: Env,
         creator: Address,
@@ -88,62 +325,31 @@ impl Envelope {
         denom: Symbol,
         expiry_secs: u64,
     ) -> u64 {
-        if amount_in <= 0 {
-            panic_with_error!(&env, Err::AmountZero);
-        }
-        creator.require_auth();
+        create(&env, creator, recipient, asset, amount_in, denom, expiry_secs, 0)
+    }
 
-        let FxPrice { ts: last_ts, .. } = last_usd(&env);
-        let cur = now(&env);
-        if cur.saturating_sub(last_ts) > 60 {
-            panic_with_error!(&env, Err::PriceStale);
+    /// Like `create_envelope`, but locks the TWAP over the last `twap_window` seconds instead of spot.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_envelope_twap(
+        env: Env,
+        creator: Address,
+        recipient: Address,
+        asset: Address,
+        amount_in: i128,
+        denom: Symbol,
+        expiry_secs: u64,
+        twap_window: u64,
+    ) -> u64 {
+        if twap_window == 0 {
+            panic_with_error!(&env, Err::WindowInvalid);
         }
-
-        TokenClient::new(&env, &asset).transfer(&creator, &env.current_contract_address(), &amount_in);
-
-        let mut id = env.storage().instance().get::<DataKey, u64>(&DataKey::NextId).unwrap_or(0);
-        id += 1;
-        env.storage().instance().set(&DataKey::NextId, &id);
-
-        let created_ts = cur;
-        let expiry_ts = if expiry_secs == 0 { 0 } else { created_ts.saturating_add(expiry_secs) };
-
-        let data = EnvelopeData {
-            id,
-            creator: creator.clone(),
-            recipient: recipient.clone(),
-            asset: asset.clone(),
-            amount_in,
-            created_ts,
-            denom,
-            opened: false,
-            expiry_ts,
-        };
-        env.storage().persistent().set(&DataKey::Envelope(id), &data);
-
-        env.events().publish(
-            (Symbol::new(&env, "EnvelopeCreated"),),
-            EnvelopeCreated {
-                id,
-                creator,
-                recipient,
-                asset,
-                amount_in,
-                ts: created_ts,
-            },
-        );
-
-        id
+        create(&env, creator, recipient, asset, amount_in, denom, expiry_secs, twap_window)
     }
 
     pub fn open_envelope(env: Env, recipient: Address, id: u64) -> i128 {
         recipient.require_auth();
 
-        let mut data: EnvelopeData = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Envelope(id))
-            .unwrap_or_else(|| panic_with_error!(&env, Err::NotFound));
+        let mut data = load(&env, id);
 
         if data.opened {
             panic_with_error!(&env, Err::AlreadyOpened);
@@ -155,13 +361,17 @@ impl Envelope {
             panic_with_error!(&env, Err::Expired);
         }
 
-        let FxPrice { price, scale, .. } = usd_at(&env, data.created_ts);
+        let FxPrice { price, scale, .. } = data.locked_fx.clone();
         let usd_amount = mul_div(data.amount_in, price, scale);
 
         TokenClient::new(&env, &data.asset).transfer(&env.current_contract_address(), &recipient, &data.amount_in);
 
         data.opened = true;
         env.storage().persistent().set(&DataKey::Envelope(id), &data);
+        update_stats(&env, &data.asset, -data.amount_in, |st| {
+            st.pending -= 1;
+            st.opened += 1;
+        });
 
         env.events().publish(
             (Symbol::new(&env, "EnvelopeOpened"),),
@@ -175,14 +385,61 @@ impl Envelope {
         usd_amount
     }
 
+    pub fn get_envelope(env: Env, id: u64) -> EnvelopeData {
+        load(&env, id)
+    }
+
+    /// Envelopes sent to `recipient`, oldest first, starting at index `cursor`.
+    pub fn list_by_recipient(env: Env, recipient: Address, cursor: u32, limit: u32) -> Vec<EnvelopeData> {
+        index_page(
+            &env,
+            DataKey::RecipientCount(recipient.clone()),
+            |i| DataKey::RecipientAt(recipient.clone(), i),
+            cursor,
+            limit,
+        )
+    }
+
+    /// Envelopes funded by `creator`, oldest first, starting at index `cursor`.
+    pub fn list_by_creator(env: Env, creator: Address, cursor: u32, limit: u32) -> Vec<EnvelopeData> {
+        index_page(
+            &env,
+            DataKey::CreatorCount(creator.clone()),
+            |i| DataKey::CreatorAt(creator.clone(), i),
+            cursor,
+            limit,
+        )
+    }
+
+    /// Oracle reading captured at creation; this is what `open_envelope` pays out against.
+    pub fn locked_price(env: Env, id: u64) -> FxPrice {
+        let data = load(&env, id);
+        data.locked_fx
+    }
+
+    /// Locked vs current USD value of an envelope's funds. Read-only; no auth.
+    /// The current value is not age-checked; callers should compare `price_ts` with `ts`.
+    pub fn valuation(env: Env, id: u64) -> EnvelopeValuation {
+        let data = load(&env, id);
+
+        let locked_usd = mul_div(data.amount_in, data.locked_fx.price, data.locked_fx.scale);
+        let FxPrice { price, scale, ts: price_ts } = last_usd(&env, None);
+        let current_usd = mul_div(data.amount_in, price, scale);
+
+        EnvelopeValuation {
+            id,
+            locked_usd,
+            current_usd,
+            delta: current_usd - locked_usd,
+            ts: now(&env),
+            price_ts,
+        }
+    }
+
     pub fn refund_after_expiry(env: Env, creator: Address, id: u64) {
         creator.require_auth();
 
-        let mut data: EnvelopeData = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Envelope(id))
-            .unwrap_or_else(|| panic_with_error!(&env, Err::NotFound));
+        let mut data = load(&env, id);
 
         if data.creator != creator {
             panic_with_error!(&env, Err::NotRecipient);
@@ -197,6 +454,36 @@ impl Envelope {
         TokenClient::new(&env, &data.asset).transfer(&env.current_contract_address(), &creator, &data.amount_in);
         data.opened = true;
         env.storage().persistent().set(&DataKey::Envelope(id), &data);
+        update_stats(&env, &data.asset, -data.amount_in, |st| {
+            st.pending -= 1;
+            st.refunded += 1;
+        });
+    }
+
+    /// Aggregate counters and the outstanding amount for each of `assets`.
+    pub fn stats(env: Env, assets: Vec<Address>) -> EnvelopeStats {
+        let Counters { total, pending, opened, refunded } = load_counters(&env);
+        let mut by_asset = Map::new(&env);
+        for asset in assets.iter() {
+            by_asset.set(asset.clone(), locked(&env, &asset));
+        }
+        EnvelopeStats { total, pending, opened, refunded, locked: by_asset }
+    }
+
+    /// Outstanding envelope amounts for each of `assets` against the contract's token balance.
+    pub fn solvency(env: Env, assets: Vec<Address>) -> Vec<SolvencyReport> {
+        let mut reports = Vec::new(&env);
+        for asset in assets.iter() {
+            let liabilities = locked(&env, &asset);
+            let balance = TokenClient::new(&env, &asset).balance(&env.current_contract_address());
+            reports.push_back(SolvencyReport {
+                asset,
+                liabilities,
+                balance,
+                shortfall: (liabilities - balance).max(0),
+            });
+        }
+        reports
     }
 }
 
@@ -204,6 +491,7 @@ use soroban_sdk::contractclient;
 #[contractclient(name = "TokenClient")]
 pub trait TokenTrait {
     fn transfer(e: Env, from: Address, to: Address, amount: i128);
+    fn balance(e: Env, id: Address) -> i128;
 }
 
 #[cfg(test)]
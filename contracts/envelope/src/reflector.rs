@@ -1,5 +1,12 @@
-use soroban_sdk::{contractclient, symbol, Address, Env, Symbol};
+use soroban_sdk::{
+    contractclient, contracttype, panic_with_error, symbol_short, vec, Address, Env, Symbol, Vec,
+};
 
+use crate::{DataKey, Err};
+
+const BPS: u128 = 10_000;
+
+#[contracttype]
 #[derive(Clone, Debug)]
 pub struct FxPrice {
     pub price: i128,
@@ -7,25 +14,231 @@ pub struct FxPrice {
     pub ts: u64,
 }
 
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OracleMode {
+    Fallback,
+    Median,
+}
+
+#[allow(dead_code)]
 #[contractclient(name = "ReflectorFxClient")]
 pub trait ReflectorFx {
     fn lastprice(e: Env, symbol: Symbol) -> (i128, i128, u64);
     fn price(e: Env, symbol: Symbol, ts: u64) -> (i128, i128, u64);
+    fn resolution(e: Env) -> u32;
 }
 
 pub fn get_fx_addr(env: &Env) -> Address {
     env.storage()
         .instance()
-        .get::<Symbol, Address>(&symbol!("reflector_fx"))
+        .get::<Symbol, Address>(&Symbol::new(env, "reflector_fx"))
         .expect("reflector fx not configured (call init)")
 }
 
-pub fn last_usd(env: &Env) -> FxPrice {
-    let (p, s, t) = ReflectorFxClient::new(env, &get_fx_addr(env)).lastprice(&symbol!("USD"));
-    FxPrice { price: p, scale: s, ts: t }
+/// Rejects readings that would make `mul_div` meaningless.
+fn is_valid(fx: &FxPrice) -> bool {
+    fx.price > 0 && fx.scale > 0
+}
+
+fn oracles(env: &Env) -> Vec<Address> {
+    match env.storage().instance().get::<DataKey, Vec<Address>>(&DataKey::Oracles) {
+        Some(list) if !list.is_empty() => list,
+        _ => vec![env, get_fx_addr(env)],
+    }
+}
+
+fn mode(env: &Env) -> OracleMode {
+    env.storage()
+        .instance()
+        .get(&DataKey::OracleMode)
+        .unwrap_or(OracleMode::Fallback)
+}
+
+/// `a < b` once both are brought to a common scale.
+fn less(env: &Env, a: &FxPrice, b: &FxPrice) -> bool {
+    match (a.price.checked_mul(b.scale), b.price.checked_mul(a.scale)) {
+        (Some(l), Some(r)) => l < r,
+        _ => panic_with_error!(env, Err::PriceInvalid),
+    }
+}
+
+fn median(env: &Env, mut readings: Vec<FxPrice>) -> FxPrice {
+    // Insertion sort; oracle lists are a handful of entries.
+    for i in 1..readings.len() {
+        let mut j = i;
+        while j > 0 {
+            let (a, b) = (readings.get_unchecked(j - 1), readings.get_unchecked(j));
+            if !less(env, &b, &a) {
+                break;
+            }
+            readings.set(j - 1, b);
+            readings.set(j, a);
+            j -= 1;
+        }
+    }
+    readings.get_unchecked((readings.len() - 1) / 2)
 }
 
-pub fn usd_at(env: &Env, ts: u64) -> FxPrice {
-    let (p, s, t) = ReflectorFxClient::new(env, &get_fx_addr(env)).price(&symbol!("USD"), &ts);
-    FxPrice { price: p, scale: s, ts: t }
-}
\ No newline at end of file
+/// Oldest spot reading, in seconds, that `create_envelope` will lock.
+pub const MAX_PRICE_AGE: u64 = 60;
+
+/// Queries each configured oracle, skipping ones that fail or return invalid prices,
+/// and, with `max_age` set, readings older than that many seconds. Each reading is
+/// paired with the oracle that gave it; `Fallback` stops at the first one.
+fn readings<F>(env: &Env, fetch: F, max_age: Option<u64>) -> Vec<(Address, FxPrice)>
+where
+    F: Fn(&ReflectorFxClient) -> Option<(i128, i128, u64)>,
+{
+    let mode = mode(env);
+    let now = env.ledger().timestamp();
+    let mut readings: Vec<(Address, FxPrice)> = Vec::new(env);
+    let mut responded = false;
+    let mut stale = false;
+
+    for addr in oracles(env).iter() {
+        let Some((p, s, t)) = fetch(&ReflectorFxClient::new(env, &addr)) else {
+            continue;
+        };
+        responded = true;
+        let fx = FxPrice { price: p, scale: s, ts: t };
+        if !is_valid(&fx) {
+            continue;
+        }
+        if max_age.is_some_and(|age| now.saturating_sub(fx.ts) > age) {
+            stale = true;
+            continue;
+        }
+        readings.push_back((addr, fx));
+        if mode == OracleMode::Fallback {
+            break;
+        }
+    }
+
+    if readings.is_empty() {
+        let err = if stale {
+            Err::PriceStale
+        } else if responded {
+            Err::PriceInvalid
+        } else {
+            Err::OracleUnavailable
+        };
+        panic_with_error!(env, err);
+    }
+    readings
+}
+
+/// Median of `readings`; with `Fallback` there is only the one reading.
+fn pick(env: &Env, readings: &Vec<(Address, FxPrice)>) -> FxPrice {
+    let mut prices = Vec::new(env);
+    for (_, fx) in readings.iter() {
+        prices.push_back(fx);
+    }
+    median(env, prices)
+}
+
+fn query<F>(env: &Env, fetch: F, max_age: Option<u64>) -> FxPrice
+where
+    F: Fn(&ReflectorFxClient) -> Option<(i128, i128, u64)>,
+{
+    pick(env, &readings(env, fetch, max_age))
+}
+
+/// Compares `fx` against the reading one resolution period earlier from the same oracles
+/// that produced it, each at its own resolution, so a single-step jump is rejected while
+/// a sustained market move keeps being accepted. With `Median` the earlier readings are
+/// combined the same way.
+fn guard_deviation(env: &Env, fx: &FxPrice, spot: &Vec<(Address, FxPrice)>) {
+    let max_bps = env
+        .storage()
+        .instance()
+        .get::<DataKey, u32>(&DataKey::MaxDeviationBps)
+        .unwrap_or(0);
+    if max_bps == 0 {
+        return;
+    }
+    let mut earlier = Vec::new(env);
+    for (addr, cur) in spot.iter() {
+        let c = ReflectorFxClient::new(env, &addr);
+        let reading = c
+            .try_resolution()
+            .ok()
+            .and_then(|r| r.ok())
+            .and_then(|step| cur.ts.checked_sub(step as u64))
+            .and_then(|ts| c.try_price(&symbol_short!("USD"), &ts).ok()?.ok());
+        if let Some((p, s, t)) = reading {
+            let fx = FxPrice { price: p, scale: s, ts: t };
+            if is_valid(&fx) {
+                earlier.push_back(fx);
+            }
+        }
+    }
+    if earlier.is_empty() {
+        panic_with_error!(env, Err::OracleUnavailable);
+    }
+    let prev = median(env, earlier);
+
+    // Cross-multiply so readings with different scales compare directly.
+    let cur = fx.price.checked_mul(prev.scale);
+    let old = prev.price.checked_mul(fx.scale);
+    let (cur, old) = match (cur, old) {
+        (Some(c), Some(o)) => (c, o),
+        _ => panic_with_error!(env, Err::PriceInvalid),
+    };
+    let diff = cur.abs_diff(old);
+    let limit = (old as u128).checked_mul(max_bps as u128);
+    match (diff.checked_mul(BPS), limit) {
+        (Some(d), Some(l)) if d <= l => {}
+        _ => panic_with_error!(env, Err::PriceInvalid),
+    }
+}
+
+/// Current USD reading; with `max_age` set, only readings at most that old are used.
+pub fn last_usd(env: &Env, max_age: Option<u64>) -> FxPrice {
+    query(env, |c| c.try_lastprice(&symbol_short!("USD")).ok()?.ok(), max_age)
+}
+
+/// Like `last_usd`, but rejected if it moved more than the configured deviation from
+/// the producing oracles' previous readings.
+pub fn guarded_usd(env: &Env, max_age: Option<u64>) -> FxPrice {
+    let spot = readings(env, |c| c.try_lastprice(&symbol_short!("USD")).ok()?.ok(), max_age);
+    let fx = pick(env, &spot);
+    guard_deviation(env, &fx, &spot);
+    fx
+}
+
+/// Number of evenly spaced readings averaged by `twap_usd`.
+pub const TWAP_SAMPLES: u64 = 5;
+
+/// Average of `samples` readings spaced evenly over the last `window` seconds, ending now.
+/// Readings are normalised to the scale of the first sample before averaging.
+pub fn twap_usd(env: &Env, window: u64, samples: u64) -> FxPrice {
+    let end = env.ledger().timestamp();
+    if samples < 2 || window < samples - 1 || window > end {
+        panic_with_error!(env, Err::WindowInvalid);
+    }
+    let step = window / (samples - 1);
+    let start = end - step * (samples - 1);
+
+    let mut scale = 0i128;
+    let mut sum = 0i128;
+    for i in 0..samples {
+        let ts = start + step * i;
+        let fx = query(env, |c| c.try_price(&symbol_short!("USD"), &ts).ok()?.ok(), None);
+        if scale == 0 {
+            scale = fx.scale;
+        }
+        let total = fx
+            .price
+            .checked_mul(scale)
+            .and_then(|p| p.checked_div(fx.scale))
+            .and_then(|p| sum.checked_add(p));
+        sum = total.unwrap_or_else(|| panic_with_error!(env, Err::PriceInvalid));
+    }
+
+    let twap = FxPrice { price: sum / samples as i128, scale, ts: end };
+    if !is_valid(&twap) {
+        panic_with_error!(env, Err::PriceInvalid);
+    }
+    twap
+}
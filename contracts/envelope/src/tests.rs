@@ -1,8 +1,9 @@
 #![cfg(test)]
 
 use super::*;
+use crate::reflector::MAX_PRICE_AGE;
 use soroban_sdk::testutils::{Address as _, Ledger};
-use soroban_sdk::{contract, contractimpl, contracttype, symbol, vec, Address, BytesN, Env, Symbol};
+use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, Env, Symbol};
 
 #[contract]
 pub struct MockToken;
@@ -38,14 +39,6 @@ impl MockToken {
     }
 }
 
-#[soroban_sdk::contractclient(name = "MockTokenClient")]
-trait MockTokenIface {
-    fn init(e: Env, admin: Address);
-    fn mint(e: Env, to: Address, amount: i128);
-    fn balance(e: Env, who: Address) -> i128;
-    fn transfer(e: Env, from: Address, to: Address, amount: i128);
-}
-
 #[contract]
 pub struct MockReflectorFx;
 
@@ -54,6 +47,7 @@ pub struct MockReflectorFx;
 enum RKey {
     Last,
     AtTs(u64),
+    Resolution,
 }
 
 #[contractimpl]
@@ -76,50 +70,62 @@ impl MockReflectorFx {
             .get::<RKey, (i128, i128, u64)>(&RKey::AtTs(ts))
             .expect("price at ts not set")
     }
+    pub fn set_resolution(env: Env, secs: u32) {
+        env.storage().instance().set(&RKey::Resolution, &secs);
+    }
+    pub fn resolution(env: Env) -> u32 {
+        env.storage().instance().get(&RKey::Resolution).unwrap_or(300)
+    }
 }
 
-#[soroban_sdk::contractclient(name = "ReflectorClient")]
-trait MockReflectorIface {
-    fn set_last(e: Env, price: i128, scale: i128, ts: u64);
-    fn set_at(e: Env, ts: u64, price: i128, scale: i128);
-    fn lastprice(e: Env, symbol: Symbol) -> (i128, i128, u64);
-    fn price(e: Env, symbol: Symbol, ts: u64) -> (i128, i128, u64);
+use MockReflectorFxClient as ReflectorClient;
+
+struct Setup<'a> {
+    env: Env,
+    token: MockTokenClient<'a>,
+    refl: ReflectorClient<'a>,
+    envlp: EnvelopeClient<'a>,
+    creator: Address,
+    recipient: Address,
 }
 
-#[soroban_sdk::contractclient(name = "EnvelopeClient")]
-trait EnvelopeIface {
-    fn init(e: Env, reflector_fx: Address);
-    fn create_envelope(
-        e: Env,
-        creator: Address,
-        recipient: Address,
-        asset: Address,
-        amount_in: i128,
-        denom: Symbol,
-        expiry_secs: u64,
-    ) -> u64;
-    fn open_envelope(e: Env, recipient: Address, id: u64) -> i128;
-    fn refund_after_expiry(e: Env, creator: Address, id: u64);
+fn setup<'a>(ts: u64) -> Setup<'a> {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|l| l.timestamp = ts);
+
+    let token = MockTokenClient::new(&env, &env.register_contract(None, MockToken));
+    let refl = ReflectorClient::new(&env, &env.register_contract(None, MockReflectorFx));
+    let envlp = EnvelopeClient::new(&env, &env.register_contract(None, Envelope));
+
+    let creator = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token.init(&creator);
+    token.mint(&creator, &1_000_000);
+    envlp.init(&creator, &refl.address);
+
+    Setup { env, token, refl, envlp, creator, recipient }
 }
 
-fn addr_of(env: &Env, id: &BytesN<32>) -> Address {
-    Address::from_contract_id(id)
+fn contract_err(e: Err) -> soroban_sdk::Error {
+    e.into()
 }
 
 #[test]
 fn create_and_open_happy_path() {
     let env = Env::default();
+    env.mock_all_auths();
     env.ledger().with_mut(|l| l.timestamp = 1_700_000_000);
 
     let token_id = env.register_contract(None, MockToken);
-    let token = MockTokenClient::new(&env, &addr_of(&env, &token_id));
+    let token = MockTokenClient::new(&env, &token_id);
 
     let refl_id = env.register_contract(None, MockReflectorFx);
-    let reflector_addr = addr_of(&env, &refl_id);
+    let reflector_addr = refl_id.clone();
     let refl = ReflectorClient::new(&env, &reflector_addr);
 
     let envlp_id = env.register_contract(None, Envelope);
-    let envlp = EnvelopeClient::new(&env, &addr_of(&env, &envlp_id));
+    let envlp = EnvelopeClient::new(&env, &envlp_id);
 
     let creator = Address::generate(&env);
     let recipient = Address::generate(&env);
@@ -127,21 +133,20 @@ fn create_and_open_happy_path() {
     token.mint(&creator, &1_000_000);
     let now = env.ledger().timestamp();
     refl.set_last(&100_000_000, &100_000_000, &now);
-    refl.set_at(&now, &100_000_000, &100_000_000);
 
-    envlp.init(&reflector_addr);
+    envlp.init(&creator, &reflector_addr);
 
     let id = envlp.create_envelope(
         &creator,
         &recipient,
-        &addr_of(&env, &token_id),
+        &token_id,
         &250_000,
-        &symbol!("USD"),
+        &symbol_short!("USD"),
         &0,
     );
     assert_eq!(id, 1);
 
-    let contract_addr = Address::from_contract_id(&envlp_id);
+    let contract_addr = envlp_id.clone();
     assert_eq!(token.balance(&creator), 750_000);
     assert_eq!(token.balance(&contract_addr), 250_000);
 
@@ -155,17 +160,18 @@ fn create_and_open_happy_path() {
 #[test]
 fn stale_price_rejected() {
     let env = Env::default();
+    env.mock_all_auths();
     env.ledger().with_mut(|l| l.timestamp = 2_000);
 
     let token_id = env.register_contract(None, MockToken);
-    let token = MockTokenClient::new(&env, &addr_of(&env, &token_id));
+    let token = MockTokenClient::new(&env, &token_id);
 
     let refl_id = env.register_contract(None, MockReflectorFx);
-    let reflector_addr = addr_of(&env, &refl_id);
+    let reflector_addr = refl_id.clone();
     let refl = ReflectorClient::new(&env, &reflector_addr);
 
     let envlp_id = env.register_contract(None, Envelope);
-    let envlp = EnvelopeClient::new(&env, &addr_of(&env, &envlp_id));
+    let envlp = EnvelopeClient::new(&env, &envlp_id);
 
     let creator = Address::generate(&env);
     let recipient = Address::generate(&env);
@@ -173,35 +179,34 @@ fn stale_price_rejected() {
     token.mint(&creator, &10);
 
     refl.set_last(&100, &100, &(env.ledger().timestamp() - 120));
-    envlp.init(&reflector_addr);
+    envlp.init(&creator, &reflector_addr);
 
-    let res = std::panic::catch_unwind(|| {
-        let _ = envlp.create_envelope(
-            &creator,
-            &recipient,
-            &addr_of(&env, &token_id),
-            &10,
-            &symbol!("USD"),
-            &0,
-        );
-    });
+    let res = envlp.try_create_envelope(
+        &creator,
+        &recipient,
+        &token_id,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
     assert!(res.is_err(), "expected stale price panic");
 }
 
 #[test]
 fn double_open_fails_and_refund_after_expiry_works() {
     let env = Env::default();
+    env.mock_all_auths();
     env.ledger().with_mut(|l| l.timestamp = 10_000);
 
     let token_id = env.register_contract(None, MockToken);
-    let token = MockTokenClient::new(&env, &addr_of(&env, &token_id));
+    let token = MockTokenClient::new(&env, &token_id);
 
     let refl_id = env.register_contract(None, MockReflectorFx);
-    let reflector_addr = addr_of(&env, &refl_id);
+    let reflector_addr = refl_id.clone();
     let refl = ReflectorClient::new(&env, &reflector_addr);
 
     let envlp_id = env.register_contract(None, Envelope);
-    let envlp = EnvelopeClient::new(&env, &addr_of(&env, &envlp_id));
+    let envlp = EnvelopeClient::new(&env, &envlp_id);
 
     let creator = Address::generate(&env);
     let recipient = Address::generate(&env);
@@ -210,35 +215,595 @@ fn double_open_fails_and_refund_after_expiry_works() {
 
     let now = env.ledger().timestamp();
     refl.set_last(&200_000_000, &100_000_000, &now);
-    refl.set_at(&now, &200_000_000, &100_000_000);
 
-    envlp.init(&reflector_addr);
+    envlp.init(&creator, &reflector_addr);
     let id = envlp.create_envelope(
         &creator,
         &recipient,
-        &addr_of(&env, &token_id),
+        &token_id,
         &100,
-        &symbol!("USD"),
+        &symbol_short!("USD"),
         &30,
     );
 
     let usd = envlp.open_envelope(&recipient, &id);
     assert_eq!(usd, 200);
 
-    let again = std::panic::catch_unwind(|| envlp.open_envelope(&recipient, &id));
+    let again = envlp.try_open_envelope(&recipient, &id);
     assert!(again.is_err(), "double open must fail");
 
     let id2 = envlp.create_envelope(
         &creator,
         &recipient,
-        &addr_of(&env, &token_id),
+        &token_id,
         &50,
-        &symbol!("USD"),
+        &symbol_short!("USD"),
         &10,
     );
     env.ledger().with_mut(|l| l.timestamp += 11);
     envlp.refund_after_expiry(&creator, &id2);
 
-    let contract_addr = Address::from_contract_id(&envlp_id);
+    let contract_addr = envlp_id.clone();
     assert_eq!(token.balance(&contract_addr), 0, "refund emptied escrow");
-}
\ No newline at end of file
+}
+
+#[test]
+fn non_positive_price_rejected() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+
+    for (price, scale) in [(0, 100), (-5, 100), (100, 0)] {
+        s.refl.set_last(&price, &scale, &now);
+        let res = s.envlp.try_create_envelope(
+            &s.creator,
+            &s.recipient,
+            &s.token.address,
+            &10,
+            &symbol_short!("USD"),
+            &0,
+        );
+        assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::PriceInvalid));
+    }
+    assert_eq!(s.token.balance(&s.creator), 1_000_000);
+}
+
+#[test]
+fn deviation_guard_rejects_jump() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.envlp.set_max_deviation(&s.creator, &500);
+    s.refl.set_at(&(now - 300), &100_000_000, &100_000_000);
+
+    s.refl.set_last(&100_000_000, &100_000_000, &now);
+    s.envlp
+        .create_envelope(&s.creator, &s.recipient, &s.token.address, &10, &symbol_short!("USD"), &0);
+
+    // +4% with a different scale is within the 5% band.
+    s.refl.set_last(&1_040, &1_000, &now);
+    s.envlp
+        .create_envelope(&s.creator, &s.recipient, &s.token.address, &10, &symbol_short!("USD"), &0);
+
+    // +14% within one resolution period is not.
+    s.refl.set_last(&1_144, &1_000, &now);
+    let res = s.envlp.try_create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::PriceInvalid));
+}
+
+#[test]
+fn deviation_guard_follows_sustained_move() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.envlp.set_max_deviation(&s.creator, &500);
+    s.refl.set_resolution(&60);
+
+    s.refl.set_at(&(now - 60), &1_000, &1_000);
+    s.refl.set_last(&1_000, &1_000, &now);
+    s.envlp
+        .create_envelope(&s.creator, &s.recipient, &s.token.address, &10, &symbol_short!("USD"), &0);
+
+    // An hour later the market is up 20%, reached in small steps.
+    let later = now + 3_600;
+    s.env.ledger().with_mut(|l| l.timestamp = later);
+    s.refl.set_at(&(later - 60), &1_180, &1_000);
+    s.refl.set_last(&1_200, &1_000, &later);
+    let id = s
+        .envlp
+        .create_envelope(&s.creator, &s.recipient, &s.token.address, &10, &symbol_short!("USD"), &0);
+    assert_eq!(s.envlp.locked_price(&id).price, 1_200);
+}
+
+#[test]
+fn set_max_deviation_requires_admin() {
+    let s = setup(5_000);
+    let res = s.envlp.try_set_max_deviation(&s.recipient, &100);
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::NotAdmin));
+}
+
+#[test]
+fn init_only_once() {
+    let s = setup(5_000);
+    let evil = oracle_with(&s.env, Some((1, 1)));
+    let res = s.envlp.try_init(&s.recipient, &evil.address);
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::AlreadyInitialized));
+
+    // The original admin keeps control.
+    s.envlp.set_max_deviation(&s.creator, &100);
+    let res = s.envlp.try_set_max_deviation(&s.recipient, &100);
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::NotAdmin));
+}
+
+#[test]
+fn init_requires_admin_auth() {
+    let env = Env::default();
+    let envlp = EnvelopeClient::new(&env, &env.register_contract(None, Envelope));
+    let admin = Address::generate(&env);
+    let refl = Address::generate(&env);
+    assert!(envlp.try_init(&admin, &refl).is_err());
+
+    env.mock_all_auths();
+    envlp.init(&admin, &refl);
+    assert_eq!(env.auths()[0].0, admin);
+}
+
+fn oracle_with<'a>(env: &Env, price: Option<(i128, i128)>) -> ReflectorClient<'a> {
+    let refl = ReflectorClient::new(env, &env.register_contract(None, MockReflectorFx));
+    if let Some((p, sc)) = price {
+        refl.set_last(&p, &sc, &env.ledger().timestamp());
+    }
+    refl
+}
+
+#[test]
+fn fallback_skips_failing_oracle() {
+    let s = setup(5_000);
+    let down = oracle_with(&s.env, None);
+    let backup = oracle_with(&s.env, Some((300, 100)));
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![&s.env, down.address.clone(), backup.address.clone()],
+        &OracleMode::Fallback,
+    );
+
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(s.envlp.locked_price(&id).price, 300);
+}
+
+#[test]
+fn fallback_skips_stale_oracle() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    let stale = oracle_with(&s.env, None);
+    stale.set_last(&100, &100, &(now - MAX_PRICE_AGE - 1));
+    let backup = oracle_with(&s.env, Some((300, 100)));
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![&s.env, stale.address.clone(), backup.address.clone()],
+        &OracleMode::Fallback,
+    );
+
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(s.envlp.locked_price(&id).price, 300);
+
+    // With only stale readings left the price is reported stale.
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![&s.env, stale.address.clone()],
+        &OracleMode::Fallback,
+    );
+    let res = s.envlp.try_create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::PriceStale));
+}
+
+#[test]
+fn deviation_guard_uses_fallback_oracles_own_history() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.envlp.set_max_deviation(&s.creator, &500);
+
+    // The primary's spot is stale, so the backup prices the envelope; the primary's
+    // older history must not be mistaken for the backup's.
+    let primary = oracle_with(&s.env, None);
+    primary.set_last(&1_000, &1_000, &(now - MAX_PRICE_AGE - 1));
+    primary.set_resolution(&60);
+    primary.set_at(&(now - 60), &1_000, &1_000);
+    let backup = oracle_with(&s.env, Some((1_500, 1_000)));
+    backup.set_at(&(now - 300), &1_480, &1_000);
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![&s.env, primary.address.clone(), backup.address.clone()],
+        &OracleMode::Fallback,
+    );
+
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(s.envlp.locked_price(&id).price, 1_500);
+}
+
+#[test]
+fn deviation_guard_median_uses_each_oracles_resolution() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.envlp.set_max_deviation(&s.creator, &500);
+
+    let a = oracle_with(&s.env, Some((1_010, 1_000)));
+    a.set_resolution(&60);
+    a.set_at(&(now - 60), &1_000, &1_000);
+    let b = oracle_with(&s.env, Some((1_030, 1_000)));
+    b.set_at(&(now - 300), &1_020, &1_000);
+    let c = oracle_with(&s.env, Some((1_000, 1_000)));
+    c.set_resolution(&120);
+    c.set_at(&(now - 120), &990, &1_000);
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![&s.env, a.address.clone(), b.address.clone(), c.address.clone()],
+        &OracleMode::Median,
+    );
+
+    // Median spot 1.010 against median earlier reading 1.000.
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(s.envlp.locked_price(&id).price, 1_010);
+
+    // All three jump together: the median moves 20% in one step.
+    a.set_last(&1_210, &1_000, &now);
+    b.set_last(&1_230, &1_000, &now);
+    c.set_last(&1_200, &1_000, &now);
+    let res = s.envlp.try_create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::PriceInvalid));
+}
+
+#[test]
+fn all_oracles_down_is_unavailable() {
+    let s = setup(5_000);
+    let down = oracle_with(&s.env, None);
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![&s.env, down.address.clone()],
+        &OracleMode::Median,
+    );
+
+    let res = s.envlp.try_create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+    );
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::OracleUnavailable));
+}
+
+#[test]
+fn median_ignores_outlier_and_failures() {
+    let s = setup(5_000);
+    let a = oracle_with(&s.env, Some((100, 100)));
+    let b = oracle_with(&s.env, Some((9_000, 100)));
+    let c = oracle_with(&s.env, Some((1_050, 1_000)));
+    let down = oracle_with(&s.env, None);
+    let bad = oracle_with(&s.env, Some((0, 100)));
+    s.envlp.set_oracles(
+        &s.creator,
+        &soroban_sdk::vec![
+            &s.env,
+            b.address.clone(),
+            down.address.clone(),
+            a.address.clone(),
+            bad.address.clone(),
+            c.address.clone(),
+        ],
+        &OracleMode::Median,
+    );
+
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &1_000,
+        &symbol_short!("USD"),
+        &0,
+    );
+    // Valid readings are 1.00, 1.05 and 90.00; the median prices the envelope.
+    assert_eq!(s.envlp.open_envelope(&s.recipient, &id), 1_050);
+}
+
+#[test]
+fn open_uses_locked_price_without_history() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.refl.set_last(&150, &100, &now);
+
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &1_000,
+        &symbol_short!("USD"),
+        &0,
+    );
+    let locked = s.envlp.locked_price(&id);
+    assert_eq!((locked.price, locked.scale, locked.ts), (150, 100, now));
+
+    // The oracle moves on and keeps no history; opening still pays at 1.50.
+    s.env.ledger().with_mut(|l| l.timestamp += 3_600);
+    s.refl.set_last(&90, &100, &s.env.ledger().timestamp());
+    assert_eq!(s.envlp.open_envelope(&s.recipient, &id), 1_500);
+}
+
+#[test]
+fn twap_envelope_locks_average_price() {
+    let s = setup(10_000);
+    let now = s.env.ledger().timestamp();
+    s.refl.set_last(&500, &100, &now);
+    // Window of 400s sampled every 100s; one reading uses a different scale.
+    s.refl.set_at(&(now - 400), &100, &100);
+    s.refl.set_at(&(now - 300), &1_200, &1_000);
+    s.refl.set_at(&(now - 200), &140, &100);
+    s.refl.set_at(&(now - 100), &160, &100);
+    s.refl.set_at(&now, &500, &100);
+
+    let id = s.envlp.create_envelope_twap(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &1_000,
+        &symbol_short!("USD"),
+        &0,
+        &400,
+    );
+    let locked = s.envlp.locked_price(&id);
+    assert_eq!((locked.price, locked.scale), (204, 100));
+    assert_eq!(s.envlp.open_envelope(&s.recipient, &id), 2_040);
+}
+
+#[test]
+fn twap_missing_history_or_bad_window_rejected() {
+    let s = setup(10_000);
+    let now = s.env.ledger().timestamp();
+    s.refl.set_last(&100, &100, &now);
+
+    let res = s.envlp.try_create_envelope_twap(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+        &0,
+    );
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::WindowInvalid));
+
+    let res = s.envlp.try_create_envelope_twap(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &10,
+        &symbol_short!("USD"),
+        &0,
+        &400,
+    );
+    assert_eq!(res.err().unwrap().unwrap(), contract_err(Err::OracleUnavailable));
+    assert_eq!(s.token.balance(&s.creator), 1_000_000);
+}
+
+#[test]
+fn valuation_reports_locked_current_and_delta() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.refl.set_last(&100, &100, &now);
+    let id = s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &50,
+        &symbol_short!("USD"),
+        &0,
+    );
+
+    s.refl.set_last(&94, &100, &now);
+    let v = s.envlp.valuation(&id);
+    assert_eq!((v.locked_usd, v.current_usd, v.delta), (50, 47, -3));
+    assert_eq!((v.ts, v.price_ts), (now, now));
+
+    // A reading hours old is still used, but its age is visible to the caller.
+    s.env.ledger().with_mut(|l| l.timestamp = now + 7_200);
+    let v = s.envlp.valuation(&id);
+    assert_eq!((v.current_usd, v.ts, v.price_ts), (47, now + 7_200, now));
+
+    // Viewing leaves the envelope untouched.
+    assert_eq!(s.envlp.open_envelope(&s.recipient, &id), 50);
+}
+
+#[test]
+fn get_and_list_envelopes_paginated() {
+    let s = setup(5_000);
+    let now = s.env.ledger().timestamp();
+    s.refl.set_last(&100, &100, &now);
+    let other = Address::generate(&s.env);
+
+    for i in 0..5 {
+        let to = if i % 2 == 0 { &s.recipient } else { &other };
+        s.envlp.create_envelope(
+            &s.creator,
+            to,
+            &s.token.address,
+            &(10 + i),
+            &symbol_short!("USD"),
+            &0,
+        );
+    }
+
+    let e = s.envlp.get_envelope(&3);
+    assert_eq!((e.id, e.amount_in, e.recipient.clone()), (3, 12, s.recipient.clone()));
+    assert_eq!(
+        s.envlp.try_get_envelope(&99).err().unwrap().unwrap(),
+        contract_err(Err::NotFound)
+    );
+
+    let page = s.envlp.list_by_recipient(&s.recipient, &0, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!((page.get(0).unwrap().id, page.get(1).unwrap().id), (1, 3));
+    let page = s.envlp.list_by_recipient(&s.recipient, &2, &2);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().id, 5);
+    assert_eq!(s.envlp.list_by_recipient(&other, &0, &10).len(), 2);
+
+    assert_eq!(s.envlp.list_by_creator(&s.creator, &0, &100).len(), 5);
+    assert_eq!(s.envlp.list_by_creator(&s.creator, &4, &100).len(), 1);
+    assert_eq!(s.envlp.list_by_creator(&other, &0, &10).len(), 0);
+}
+
+#[test]
+fn stats_track_create_open_and_refund() {
+    let s = setup(5_000);
+    s.refl.set_last(&100, &100, &s.env.ledger().timestamp());
+    let usd = symbol_short!("USD");
+
+    let a = s.envlp.create_envelope(&s.creator, &s.recipient, &s.token.address, &100, &usd, &0);
+    let b = s.envlp.create_envelope(&s.creator, &s.recipient, &s.token.address, &40, &usd, &10);
+    s.envlp.create_envelope(&s.creator, &s.recipient, &s.token.address, &7, &usd, &0);
+
+    let st = s.envlp.stats(&soroban_sdk::vec![&s.env, s.token.address.clone()]);
+    assert_eq!((st.total, st.pending, st.opened, st.refunded), (3, 3, 0, 0));
+    assert_eq!(st.locked.get(s.token.address.clone()), Some(147));
+
+    s.envlp.open_envelope(&s.recipient, &a);
+    s.env.ledger().with_mut(|l| l.timestamp += 11);
+    s.envlp.refund_after_expiry(&s.creator, &b);
+
+    let st = s.envlp.stats(&soroban_sdk::vec![&s.env, s.token.address.clone()]);
+    assert_eq!((st.total, st.pending, st.opened, st.refunded), (3, 1, 1, 1));
+    assert_eq!(st.locked.get(s.token.address.clone()), Some(7));
+    assert_eq!(s.token.balance(&s.envlp.address), 7);
+
+    // Only the requested assets are reported; unused ones show nothing locked.
+    let unused = Address::generate(&s.env);
+    let st = s.envlp.stats(&soroban_sdk::vec![&s.env, unused.clone()]);
+    assert_eq!((st.total, st.locked.len()), (3, 1));
+    assert_eq!(st.locked.get(unused), Some(0));
+}
+
+/// xorshift64; enough randomness to shuffle operation sequences deterministically.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+fn assert_solvent(s: &Setup) {
+    for r in s.envlp.solvency(&soroban_sdk::vec![&s.env, s.token.address.clone()]).iter() {
+        assert_eq!(r.shortfall, 0);
+        assert_eq!(r.liabilities, r.balance);
+    }
+}
+
+#[test]
+fn solvency_holds_after_random_operations() {
+    for seed in 1..=8u64 {
+        let s = setup(5_000);
+        let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let usd = symbol_short!("USD");
+        let mut ids: Vec<u64> = Vec::new(&s.env);
+
+        for _ in 0..40 {
+            s.refl.set_last(&100, &100, &s.env.ledger().timestamp());
+            match rng.next(4) {
+                0 | 1 => {
+                    let amount = 1 + rng.next(1_000) as i128;
+                    let expiry = rng.next(3) * 20;
+                    ids.push_back(s.envlp.create_envelope(
+                        &s.creator,
+                        &s.recipient,
+                        &s.token.address,
+                        &amount,
+                        &usd,
+                        &expiry,
+                    ));
+                }
+                2 if !ids.is_empty() => {
+                    let id = ids.get_unchecked(rng.next(ids.len() as u64) as u32);
+                    let _ = s.envlp.try_open_envelope(&s.recipient, &id);
+                }
+                3 if !ids.is_empty() => {
+                    let id = ids.get_unchecked(rng.next(ids.len() as u64) as u32);
+                    let _ = s.envlp.try_refund_after_expiry(&s.creator, &id);
+                }
+                _ => s.env.ledger().with_mut(|l| l.timestamp += 15),
+            }
+            assert_solvent(&s);
+        }
+
+        let st = s.envlp.stats(&soroban_sdk::vec![&s.env, s.token.address.clone()]);
+        assert_eq!(st.total, st.pending + st.opened + st.refunded);
+    }
+}
+
+#[test]
+fn solvency_reports_shortfall() {
+    let s = setup(5_000);
+    s.refl.set_last(&100, &100, &s.env.ledger().timestamp());
+    s.envlp.create_envelope(
+        &s.creator,
+        &s.recipient,
+        &s.token.address,
+        &300,
+        &symbol_short!("USD"),
+        &0,
+    );
+
+    // Simulate funds leaking out of the pooled balance.
+    s.token.transfer(&s.envlp.address, &s.recipient, &120);
+    let r = s.envlp.solvency(&soroban_sdk::vec![&s.env, s.token.address.clone()]).get(0).unwrap();
+    assert_eq!((r.liabilities, r.balance, r.shortfall), (300, 180, 120));
+}
@@ -0,0 +1,84 @@
+use soroban_sdk::{contracttype, panic_with_error, Address, BytesN, Env, Vec};
+
+use crate::{DataKey, EscrowError};
+
+/// Guardians allowed to approve forced refunds and how many must agree
+#[derive(Clone)]
+#[contracttype]
+pub struct GuardianSet {
+    pub guardians: Vec<Address>,
+    pub threshold: u32,
+    pub proposal_ttl: u64, // seconds a proposal stays open
+}
+
+/// A forced refund waiting for guardian approvals
+#[derive(Clone)]
+#[contracttype]
+pub struct RefundProposal {
+    pub escrow_id: BytesN<32>,
+    pub proposer: Address,
+    pub reason: u32,
+    pub approvals: Vec<Address>,
+    pub expires_at: u64,
+    pub executed: bool,
+}
+
+pub fn get_set(env: &Env) -> Option<GuardianSet> {
+    env.storage().instance().get(&DataKey::Guardians)
+}
+
+pub fn validate_set(env: &Env, set: &GuardianSet) {
+    let n = set.guardians.len();
+    if set.threshold == 0 || set.threshold > n || set.proposal_ttl == 0 {
+        panic_with_error!(env, EscrowError::InvalidGuardianSet);
+    }
+    for i in 0..n {
+        let g = set.guardians.get_unchecked(i);
+        if set.guardians.last_index_of(&g) != Some(i) {
+            panic_with_error!(env, EscrowError::InvalidGuardianSet);
+        }
+    }
+}
+
+/// Require `guardian`'s auth and membership of the current set
+pub fn require_guardian(env: &Env, guardian: &Address) -> GuardianSet {
+    guardian.require_auth();
+    let set = get_set(env).unwrap_or_else(|| panic_with_error!(env, EscrowError::NotGuardian));
+    if !set.guardians.contains(guardian) {
+        panic_with_error!(env, EscrowError::NotGuardian);
+    }
+    set
+}
+
+/// Require auth from at least `set.threshold` distinct members of `set`
+pub fn require_quorum(env: &Env, set: &GuardianSet, approvers: &Vec<Address>) {
+    for i in 0..approvers.len() {
+        let approver = approvers.get_unchecked(i);
+        if !set.guardians.contains(&approver) {
+            panic_with_error!(env, EscrowError::NotGuardian);
+        }
+        if approvers.first_index_of(&approver) != Some(i) {
+            panic_with_error!(env, EscrowError::AlreadyApproved);
+        }
+        approver.require_auth();
+    }
+    if approvers.len() < set.threshold {
+        panic_with_error!(env, EscrowError::GuardianApprovalRequired);
+    }
+}
+
+pub fn load_proposal(env: &Env, proposal_id: u64) -> RefundProposal {
+    env.storage()
+        .persistent()
+        .get(&DataKey::RefundProposal(proposal_id))
+        .unwrap_or_else(|| panic_with_error!(env, EscrowError::ProposalNotFound))
+}
+
+/// Approvals that still belong to members of the current guardian set
+pub fn approval_count(set: &GuardianSet, proposal: &RefundProposal) -> u32 {
+    proposal
+        .approvals
+        .iter()
+        .filter(|a| set.guardians.contains(a))
+        .count() as u32
+}
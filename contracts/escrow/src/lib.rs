@@ -1,10 +1,80 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, token, Address, Env, Symbol, Vec,
-    log, BytesN
+    contract, contracterror, contractimpl, contracttype, panic_with_error, token, xdr::ToXdr,
+    Address, Bytes, BytesN, Env, Symbol, Vec,
 };
 
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum EscrowError {
+    NonPositiveAmount = 1,
+    ExpiryTooSoon = 2,
+    ExpiryTooFar = 3,
+    TokenBanned = 4,
+    AlreadyInitialized = 5,
+    NoPendingAdmin = 6,
+    NotGuardian = 7,
+    ProposalNotFound = 8,
+    ProposalExpired = 9,
+    AlreadyApproved = 10,
+    InvalidGuardianSet = 11,
+    GuardianApprovalRequired = 12,
+    ProposalExecuted = 13,
+    RefundNotQueued = 14,
+    TimelockActive = 15,
+    RefundAlreadyQueued = 16,
+    InvalidClaimAmount = 17,
+    NoArbiter = 18,
+    ReleaseNotApproved = 19,
+    AlreadyReleased = 20,
+    KeeperFeeTooHigh = 21,
+    BatchTooLarge = 22,
+    NotSettled = 23,
+    GracePeriodActive = 24,
+    InvalidExpiryWindow = 25,
+    RefundDelayTooShort = 26,
+    InvalidReleaseAmount = 27,
+}
+
+mod guardians;
+pub use guardians::{GuardianSet, RefundProposal};
+
+/// When an escrow becomes refundable: a ledger sequence or a ledger close timestamp
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Expiry {
+    Ledger(u32),
+    Timestamp(u64),
+}
+
+impl Expiry {
+    pub fn is_reached(&self, env: &Env) -> bool {
+        match *self {
+            Expiry::Ledger(seq) => env.ledger().sequence() >= seq,
+            Expiry::Timestamp(ts) => env.ledger().timestamp() >= ts,
+        }
+    }
+}
+
+/// Where an arbitrated escrow stands; claims need `Released`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ArbiterStatus {
+    Pending,
+    Released,
+    Disputed,
+}
+
+/// Hash function an HTLC hashlock was computed with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum HashAlgorithm {
+    Sha256,
+    Keccak256,
+}
+
 #[derive(Clone)]
 #[contracttype]
 pub struct EscrowData {
@@ -12,9 +82,19 @@ pub struct EscrowData {
     pub recipient_hash: BytesN<32>,
     pub token: Address,
     pub amount: i128,
-    pub expiry_ledger: u32,
+    pub expiry: Expiry,
     pub is_claimed: bool,
     pub is_refunded: bool,
+    pub hash_version: u32,
+    pub nonce: u64, // only meaningful for contract-generated ids
+    pub claimed_amount: i128,
+    pub arbiter: Option<Address>,
+    pub arbiter_status: ArbiterStatus,
+    pub recipient: Option<Address>, // fixed recipient of HTLC escrows
+    pub hash_algorithm: HashAlgorithm, // HTLC hashlock function
+    pub claim_key: Option<Bytes>, // 32-byte ed25519 key of signed-claim escrows
+    pub settled_at: u64, // ledger timestamp of the final claim or refund
+    pub unlocked_amount: i128, // claimable so far; below `amount` only for milestone escrows
 }
 
 #[derive(Clone)]
@@ -22,11 +102,811 @@ pub struct EscrowData {
 pub enum DataKey {
     Escrow(BytesN<32>), // escrow_id
     Admin,
+    Outstanding(Address), // token
+    Nonce(Address), // sender
+    ExpiryWindow,
+    Banned(Address), // token
+    PendingAdmin,
+    Guardians,
+    NextProposalId,
+    RefundProposal(u64), // proposal_id
+    RefundDelay,
+    QueuedRefund(BytesN<32>), // escrow_id
+    SenderCount(Address), // sender
+    SenderAt(Address, u32), // sender, index
+    KeeperFeeBps,
+    Tombstone(BytesN<32>), // escrow_id
+    ArchiveGrace,
+}
+
+/// Upper bound for the keeper incentive on expired refunds, in basis points
+pub const MAX_KEEPER_FEE_BPS: u32 = 500;
+
+/// Lifecycle state of an escrow as seen by its sender
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum EscrowStatus {
+    Active,
+    Claimed,
+    Refunded,
+    Expired, // unsettled and past expiry, so refundable
+}
+
+/// An escrow together with its id and current status
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowEntry {
+    pub escrow_id: BytesN<32>,
+    pub status: EscrowStatus,
+    pub escrow: EscrowData,
+}
+
+/// Most index slots `list_escrows_by_sender` reads per call
+pub const MAX_PAGE: u32 = 50;
+
+/// One escrow of a `create_escrows_batch` call
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowSpec {
+    pub escrow_id: BytesN<32>, // scoped to the sender as in `create_escrow`
+    pub recipient_hash: BytesN<32>,
+    pub amount: i128,
+    pub expiry: Expiry,
+}
+
+/// Result for one id of `refund_expired_batch`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum RefundOutcome {
+    Refunded,
+    NotFound, // never created, or an archived nonce-derived escrow
+    NotExpired,
+    Settled, // already claimed or refunded, including archived escrows with a tombstone
+    Failed, // the token transfer to the sender failed; the escrow stays refundable
+}
+
+/// Most entries a batch call accepts
+pub const MAX_BATCH: u32 = 100;
+
+/// What remains of an archived escrow with a caller-supplied id, so the id stays taken
+#[derive(Clone)]
+#[contracttype]
+pub struct Tombstone {
+    pub sender: Address,
+    pub status: EscrowStatus, // Claimed or Refunded
+    pub settled_at: u64,
+}
+
+/// Seconds a settled escrow is kept in full before `archive` may compact it, unless configured
+pub const DEFAULT_ARCHIVE_GRACE: u64 = 30 * 86_400;
+
+/// A forced refund waiting out the timelock; the recipient can still claim meanwhile
+#[derive(Clone)]
+#[contracttype]
+pub struct QueuedRefund {
+    pub reason: u32,
+    pub executable_at: u64,
 }
 
-const ESCROW_CLAIMED: Symbol = Symbol::new(&soroban_sdk::Env::new(), "escrow_claimed");
-const ESCROW_REFUNDED: Symbol = Symbol::new(&soroban_sdk::Env::new(), "escrow_refunded");
-const ESCROW_CREATED: Symbol = Symbol::new(&soroban_sdk::Env::new(), "escrow_created");
+/// Seconds a queued admin refund waits before it can execute, unless configured
+pub const DEFAULT_REFUND_DELAY: u64 = 86_400;
+
+/// Shortest refund delay the admin may configure, so a queued refund stays visible
+/// long enough for the sender to react
+pub const MIN_REFUND_DELAY: u64 = 3_600;
+
+#[derive(Clone)]
+#[contracttype]
+pub struct AdminChanged {
+    pub previous: Address,
+    pub admin: Address,
+}
+
+/// Body of `escrow_created`; topics are the event name, escrow id and sender
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowCreated {
+    pub escrow_id: BytesN<32>,
+    pub sender: Address,
+    pub recipient_hash: BytesN<32>,
+    pub token: Address,
+    pub amount: i128,
+    pub expiry: Expiry,
+    pub hash_version: u32,
+}
+
+/// Body of `escrow_tranche_claimed`; topics are the event name, escrow id and sender
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowTrancheClaimed {
+    pub escrow_id: BytesN<32>,
+    pub recipient: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub remaining: i128,
+}
+
+/// Body of `escrow_tranche_released`; topics are the event name, escrow id and sender
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowTrancheReleased {
+    pub escrow_id: BytesN<32>,
+    pub releaser: Address,
+    pub amount: i128,
+    pub unlocked: i128,
+}
+
+/// Body of `escrow_claimed`; `amount` is the total paid out over all tranches
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowClaimed {
+    pub escrow_id: BytesN<32>,
+    pub recipient: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub ledger: u32,
+}
+
+/// Body of `escrow_refunded`; `amount` includes any keeper fee
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowRefunded {
+    pub escrow_id: BytesN<32>,
+    pub sender: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub ledger: u32,
+}
+
+/// Body of `escrow_admin_refunded`
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowAdminRefunded {
+    pub escrow_id: BytesN<32>,
+    pub sender: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub reason: u32,
+    pub ledger: u32,
+}
+
+/// Body of `escrow_arbiter_refunded`
+#[derive(Clone)]
+#[contracttype]
+pub struct EscrowArbiterRefunded {
+    pub escrow_id: BytesN<32>,
+    pub sender: Address,
+    pub arbiter: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub ledger: u32,
+}
+
+/// Allowed distance between creation and expiry, per kind of `Expiry`
+#[derive(Clone)]
+#[contracttype]
+pub struct ExpiryWindow {
+    pub min_ledgers: u32,
+    pub max_ledgers: u32,
+    pub min_secs: u64,
+    pub max_secs: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SolvencyReport {
+    pub token: Address,
+    pub liabilities: i128,
+    pub balance: i128,
+    pub shortfall: i128,
+}
+
+const ESCROW_CLAIMED: &str = "escrow_claimed";
+const ESCROW_REFUNDED: &str = "escrow_refunded";
+const ESCROW_CREATED: &str = "escrow_created";
+const ESCROW_TRANCHE_CLAIMED: &str = "escrow_tranche_claimed";
+const ESCROW_TRANCHE_RELEASED: &str = "escrow_tranche_released";
+const ADMIN_CHANGED: &str = "admin_changed";
+const REFUND_PROPOSED: &str = "refund_proposed";
+const REFUND_APPROVED: &str = "refund_approved";
+const ADMIN_REFUND_QUEUED: &str = "admin_refund_queued";
+const ESCROW_ADMIN_REFUNDED: &str = "escrow_admin_refunded";
+const ESCROW_RELEASE_APPROVED: &str = "escrow_release_approved";
+const ESCROW_ARBITER_REFUNDED: &str = "escrow_arbiter_refunded";
+const ESCROW_DISPUTED: &str = "escrow_disputed";
+const HTLC_CLAIMED: &str = "htlc_claimed";
+const KEEPER_REWARDED: &str = "keeper_rewarded";
+const ESCROW_ARCHIVED: &str = "escrow_archived";
+
+/// `recipient_hash` scheme for escrows with caller-supplied ids
+pub const RECIPIENT_HASH_VERSION: u32 = 1;
+/// `recipient_hash` scheme for escrows with contract-generated ids
+pub const RECIPIENT_HASH_VERSION_NONCE: u32 = 2;
+/// HTLC escrows: `recipient_hash` is the hashlock over the secret, the recipient is stored
+pub const RECIPIENT_HASH_VERSION_HTLC: u32 = 3;
+/// Signed-claim escrows: no hash, claims carry a signature by `claim_key`
+pub const RECIPIENT_HASH_VERSION_SIGNED: u32 = 4;
+const RECIPIENT_HASH_TAG: &[u8] = b"novagift:escrow:recipient:v1";
+const RECIPIENT_HASH_NONCE_TAG: &[u8] = b"novagift:escrow:recipient:v2";
+const SCOPED_ID_TAG: &[u8] = b"novagift:escrow:id:v1";
+const CLAIM_MESSAGE_TAG: &[u8] = b"novagift:escrow:claim:v1";
+
+/// Compute the `recipient_hash` a claim is checked against.
+///
+/// sha256 over, in order: the tag `novagift:escrow:recipient:v1`, the XDR of the
+/// escrow contract address, the 32-byte escrow id, the XDR of the recipient
+/// address and the 32-byte claim secret. Off-chain code must hash the same bytes.
+///
+/// The escrow id is the sender-scoped id `create_escrow` returns (see
+/// `scoped_escrow_id`), not the id the caller passed in.
+pub fn recipient_hash(
+    env: &Env,
+    contract: &Address,
+    escrow_id: &BytesN<32>,
+    recipient: &Address,
+    claim_secret: &BytesN<32>,
+) -> BytesN<32> {
+    let mut input = Bytes::from_slice(env, RECIPIENT_HASH_TAG);
+    input.append(&contract.clone().to_xdr(env));
+    input.append(&escrow_id.clone().into());
+    input.append(&recipient.clone().to_xdr(env));
+    input.append(&claim_secret.clone().into());
+    env.crypto().sha256(&input).into()
+}
+
+/// Compute the `recipient_hash` for an escrow created with `create_escrow_auto`.
+///
+/// The id of such an escrow depends on the hash, so the hash binds the sender and
+/// nonce instead: sha256 over the tag `novagift:escrow:recipient:v2`, the XDR of
+/// the escrow contract address, the XDR of the sender, the nonce as 8 big-endian
+/// bytes, the XDR of the recipient address and the 32-byte claim secret.
+pub fn recipient_hash_for_nonce(
+    env: &Env,
+    contract: &Address,
+    sender: &Address,
+    nonce: u64,
+    recipient: &Address,
+    claim_secret: &BytesN<32>,
+) -> BytesN<32> {
+    let mut input = Bytes::from_slice(env, RECIPIENT_HASH_NONCE_TAG);
+    input.append(&contract.clone().to_xdr(env));
+    input.append(&sender.clone().to_xdr(env));
+    input.extend_from_array(&nonce.to_be_bytes());
+    input.append(&recipient.clone().to_xdr(env));
+    input.append(&claim_secret.clone().into());
+    env.crypto().sha256(&input).into()
+}
+
+/// Message the claim key signs to authorise `recipient` to claim a signed-claim escrow.
+///
+/// The tag `novagift:escrow:claim:v1`, the XDR of the escrow contract address, the
+/// 32-byte escrow id and the XDR of the recipient address, concatenated.
+pub fn claim_message(
+    env: &Env,
+    contract: &Address,
+    escrow_id: &BytesN<32>,
+    recipient: &Address,
+) -> Bytes {
+    let mut message = Bytes::from_slice(env, CLAIM_MESSAGE_TAG);
+    message.append(&contract.clone().to_xdr(env));
+    message.append(&escrow_id.clone().into());
+    message.append(&recipient.clone().to_xdr(env));
+    message
+}
+
+/// Id under which `create_escrow` stores a caller-supplied id: sha256 over the tag
+/// `novagift:escrow:id:v1`, the XDR of the sender and the supplied id
+pub fn scoped_escrow_id(env: &Env, sender: &Address, escrow_id: &BytesN<32>) -> BytesN<32> {
+    let mut input = Bytes::from_slice(env, SCOPED_ID_TAG);
+    input.append(&sender.clone().to_xdr(env));
+    input.append(&escrow_id.clone().into());
+    env.crypto().sha256(&input).into()
+}
+
+/// Id assigned by `create_escrow_auto`: sha256 over the XDR of the sender, the
+/// nonce as 8 big-endian bytes and the recipient hash
+pub fn derived_escrow_id(
+    env: &Env,
+    sender: &Address,
+    nonce: u64,
+    recipient_hash: &BytesN<32>,
+) -> BytesN<32> {
+    let mut input = sender.clone().to_xdr(env);
+    input.extend_from_array(&nonce.to_be_bytes());
+    input.append(&recipient_hash.clone().into());
+    env.crypto().sha256(&input).into()
+}
+
+fn require_admin(env: &Env) -> Address {
+    let admin: Address = env
+        .storage()
+        .instance()
+        .get(&DataKey::Admin)
+        .unwrap_or_else(|| panic!("Admin not set"));
+    admin.require_auth();
+    admin
+}
+
+fn expiry_window(env: &Env) -> ExpiryWindow {
+    env.storage()
+        .instance()
+        .get(&DataKey::ExpiryWindow)
+        .unwrap_or(ExpiryWindow {
+            min_ledgers: 1,
+            max_ledgers: u32::MAX,
+            min_secs: 1,
+            max_secs: u64::MAX,
+        })
+}
+
+/// Reject escrows that could never be claimed or refunded sensibly.
+///
+/// The token is not checked to be a Stellar Asset Contract: a contract cannot
+/// inspect another contract's executable, so any token contract is accepted and
+/// the admin's ban list is the control for tokens that misbehave.
+fn validate_new_escrow(env: &Env, escrow: &EscrowData) {
+    if escrow.amount <= 0 {
+        panic_with_error!(env, EscrowError::NonPositiveAmount);
+    }
+    if env
+        .storage()
+        .persistent()
+        .get(&DataKey::Banned(escrow.token.clone()))
+        .unwrap_or(false)
+    {
+        panic_with_error!(env, EscrowError::TokenBanned);
+    }
+
+    let window = expiry_window(env);
+    let (distance, min, max) = match escrow.expiry {
+        Expiry::Ledger(seq) => (
+            seq as i128 - env.ledger().sequence() as i128,
+            window.min_ledgers as i128,
+            window.max_ledgers as i128,
+        ),
+        Expiry::Timestamp(ts) => (
+            ts as i128 - env.ledger().timestamp() as i128,
+            window.min_secs as i128,
+            window.max_secs as i128,
+        ),
+    };
+    if distance < min {
+        panic_with_error!(env, EscrowError::ExpiryTooSoon);
+    }
+    if distance > max {
+        panic_with_error!(env, EscrowError::ExpiryTooFar);
+    }
+}
+
+fn escrow_status(env: &Env, escrow: &EscrowData) -> EscrowStatus {
+    if escrow.is_claimed {
+        EscrowStatus::Claimed
+    } else if escrow.is_refunded {
+        EscrowStatus::Refunded
+    } else if escrow.expiry.is_reached(env) {
+        EscrowStatus::Expired
+    } else {
+        EscrowStatus::Active
+    }
+}
+
+/// Lock the sender's funds and persist a new escrow under `escrow_id`
+fn store_new_escrow(env: &Env, escrow_id: &BytesN<32>, escrow: &EscrowData) {
+    validate_new_escrow(env, escrow);
+
+    // Transfer tokens from sender to contract
+    let token_client = token::Client::new(env, &escrow.token);
+    token_client.transfer(&escrow.sender, &env.current_contract_address(), &escrow.amount);
+
+    record_new_escrow(env, escrow_id, escrow);
+}
+
+/// Persist a validated escrow whose funds the contract has received or is about to
+fn record_new_escrow(env: &Env, escrow_id: &BytesN<32>, escrow: &EscrowData) {
+    // Check if escrow already exists, or existed and was archived
+    if env.storage().persistent().has(&DataKey::Escrow(escrow_id.clone()))
+        || env.storage().persistent().has(&DataKey::Tombstone(escrow_id.clone()))
+    {
+        panic!("Escrow already exists");
+    }
+
+    adjust_outstanding(env, &escrow.token, escrow.amount);
+    env.storage().persistent().set(&DataKey::Escrow(escrow_id.clone()), escrow);
+
+    // Index under the sender
+    let count_key = DataKey::SenderCount(escrow.sender.clone());
+    let n: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+    env.storage()
+        .persistent()
+        .set(&DataKey::SenderAt(escrow.sender.clone(), n), escrow_id);
+    env.storage().persistent().set(&count_key, &(n + 1));
+
+    // Emit event
+    env.events().publish(
+        (Symbol::new(env, ESCROW_CREATED), escrow_id.clone(), escrow.sender.clone()),
+        EscrowCreated {
+            escrow_id: escrow_id.clone(),
+            sender: escrow.sender.clone(),
+            recipient_hash: escrow.recipient_hash.clone(),
+            token: escrow.token.clone(),
+            amount: escrow.amount,
+            expiry: escrow.expiry,
+            hash_version: escrow.hash_version,
+        },
+    );
+}
+
+/// Escrow claimed with a secret against a `recipient_hash` bound to its scoped id
+/// The other kinds of escrow start from it and override the fields that differ
+fn secret_escrow(
+    sender: Address,
+    recipient_hash: BytesN<32>,
+    token: Address,
+    amount: i128,
+    expiry: Expiry,
+    arbiter: Option<Address>,
+) -> EscrowData {
+    EscrowData {
+        sender,
+        recipient_hash,
+        token,
+        amount,
+        expiry,
+        is_claimed: false,
+        is_refunded: false,
+        hash_version: RECIPIENT_HASH_VERSION,
+        nonce: 0,
+        claimed_amount: 0,
+        arbiter,
+        arbiter_status: ArbiterStatus::Pending,
+        recipient: None,
+        hash_algorithm: HashAlgorithm::Sha256,
+        claim_key: None,
+        settled_at: 0,
+        unlocked_amount: amount,
+    }
+}
+
+/// Add `guardian`'s approval and queue the refund once the threshold is met
+fn record_approval(env: &Env, set: &GuardianSet, guardian: Address, proposal_id: u64) {
+    let mut proposal = guardians::load_proposal(env, proposal_id);
+
+    if proposal.executed {
+        panic_with_error!(env, EscrowError::ProposalExecuted);
+    }
+    if env.ledger().timestamp() > proposal.expires_at {
+        panic_with_error!(env, EscrowError::ProposalExpired);
+    }
+    if proposal.approvals.contains(&guardian) {
+        panic_with_error!(env, EscrowError::AlreadyApproved);
+    }
+
+    proposal.approvals.push_back(guardian.clone());
+    let approvals = guardians::approval_count(set, &proposal);
+    env.events().publish(
+        (Symbol::new(env, REFUND_APPROVED), proposal_id),
+        (guardian, approvals, set.threshold),
+    );
+
+    if approvals >= set.threshold {
+        proposal.executed = true;
+        queue_refund(env, &proposal.escrow_id, proposal.reason);
+    }
+    env.storage()
+        .persistent()
+        .set(&DataKey::RefundProposal(proposal_id), &proposal);
+}
+
+/// Load an unsettled escrow and check `recipient` and `claim_secret` against its hash
+fn load_claimable(
+    env: &Env,
+    escrow_id: &BytesN<32>,
+    recipient: &Address,
+    claim_secret: &BytesN<32>,
+) -> EscrowData {
+    let escrow = load_unsettled(env, escrow_id);
+
+    // Verify recipient hash matches
+    let contract = env.current_contract_address();
+    let computed_hash = match escrow.hash_version {
+        RECIPIENT_HASH_VERSION => recipient_hash(env, &contract, escrow_id, recipient, claim_secret),
+        RECIPIENT_HASH_VERSION_NONCE => recipient_hash_for_nonce(
+            env,
+            &contract,
+            &escrow.sender,
+            escrow.nonce,
+            recipient,
+            claim_secret,
+        ),
+        RECIPIENT_HASH_VERSION_HTLC => {
+            if escrow.recipient.as_ref() != Some(recipient) {
+                panic!("Invalid recipient or secret");
+            }
+            let preimage = claim_secret.clone().into();
+            match escrow.hash_algorithm {
+                HashAlgorithm::Sha256 => env.crypto().sha256(&preimage).into(),
+                HashAlgorithm::Keccak256 => env.crypto().keccak256(&preimage).into(),
+            }
+        }
+        RECIPIENT_HASH_VERSION_SIGNED => panic!("Escrow requires a signed claim"),
+        _ => panic!("Unsupported hash version"),
+    };
+
+    if computed_hash != escrow.recipient_hash {
+        panic!("Invalid recipient or secret");
+    }
+    require_released(env, &escrow);
+    escrow
+}
+
+/// Arbitrated escrows can only be claimed once the arbiter approved the release
+fn require_released(env: &Env, escrow: &EscrowData) {
+    if escrow.arbiter.is_some() && escrow.arbiter_status != ArbiterStatus::Released {
+        panic_with_error!(env, EscrowError::ReleaseNotApproved);
+    }
+}
+
+/// Unlocked amount not yet claimed; a claim of nothing is rejected
+fn unclaimed_unlocked(env: &Env, escrow: &EscrowData) -> i128 {
+    let unclaimed = escrow.unlocked_amount - escrow.claimed_amount;
+    if unclaimed <= 0 {
+        panic_with_error!(env, EscrowError::InvalidClaimAmount);
+    }
+    unclaimed
+}
+
+/// Publish the preimage of a claimed HTLC so the counterparty chain can use it
+fn reveal_preimage(
+    env: &Env,
+    escrow_id: &BytesN<32>,
+    escrow: &EscrowData,
+    recipient: &Address,
+    claim_secret: BytesN<32>,
+) {
+    if escrow.hash_version == RECIPIENT_HASH_VERSION_HTLC {
+        env.events().publish(
+            (Symbol::new(env, HTLC_CLAIMED), escrow.recipient_hash.clone()),
+            (escrow_id.clone(), recipient.clone(), claim_secret),
+        );
+    }
+}
+
+/// Pay `amount` of an escrow to its recipient, settling it once nothing remains
+fn pay_tranche(
+    env: &Env,
+    escrow_id: BytesN<32>,
+    mut escrow: EscrowData,
+    recipient: Address,
+    amount: i128,
+) {
+    // Transfer tokens to recipient
+    let token_client = token::Client::new(env, &escrow.token);
+    token_client.transfer(&env.current_contract_address(), &recipient, &amount);
+
+    escrow.claimed_amount += amount;
+    adjust_outstanding(env, &escrow.token, -amount);
+    let remaining = escrow.amount - escrow.claimed_amount;
+    env.events().publish(
+        (Symbol::new(env, ESCROW_TRANCHE_CLAIMED), escrow_id.clone(), escrow.sender.clone()),
+        EscrowTrancheClaimed {
+            escrow_id: escrow_id.clone(),
+            recipient: recipient.clone(),
+            token: escrow.token.clone(),
+            amount,
+            remaining,
+        },
+    );
+
+    // Mark as claimed once fully paid out
+    if remaining == 0 {
+        escrow.is_claimed = true;
+        escrow.settled_at = env.ledger().timestamp();
+        env.storage()
+            .persistent()
+            .remove(&DataKey::QueuedRefund(escrow_id.clone()));
+        env.events().publish(
+            (Symbol::new(env, ESCROW_CLAIMED), escrow_id.clone(), escrow.sender.clone()),
+            EscrowClaimed {
+                escrow_id: escrow_id.clone(),
+                recipient,
+                token: escrow.token.clone(),
+                amount: escrow.claimed_amount,
+                ledger: env.ledger().sequence(),
+            },
+        );
+    }
+    env.storage().persistent().set(&DataKey::Escrow(escrow_id), &escrow);
+}
+
+fn refund_delay(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get(&DataKey::RefundDelay)
+        .unwrap_or(DEFAULT_REFUND_DELAY)
+}
+
+/// Schedule a forced refund after the configured delay
+fn queue_refund(env: &Env, escrow_id: &BytesN<32>, reason: u32) {
+    load_unsettled(env, escrow_id);
+    let key = DataKey::QueuedRefund(escrow_id.clone());
+    if env.storage().persistent().has(&key) {
+        panic_with_error!(env, EscrowError::RefundAlreadyQueued);
+    }
+
+    let executable_at = env.ledger().timestamp().saturating_add(refund_delay(env));
+    env.storage().persistent().set(
+        &key,
+        &QueuedRefund {
+            reason,
+            executable_at,
+        },
+    );
+    env.events().publish(
+        (Symbol::new(env, ADMIN_REFUND_QUEUED),),
+        (escrow_id.clone(), reason, executable_at),
+    );
+}
+
+/// Load an escrow that has been neither claimed nor refunded
+fn load_unsettled(env: &Env, escrow_id: &BytesN<32>) -> EscrowData {
+    let escrow: EscrowData = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Escrow(escrow_id.clone()))
+        .unwrap_or_else(|| panic!("Escrow not found"));
+
+    if escrow.is_claimed {
+        panic!("Escrow already claimed");
+    }
+    if escrow.is_refunded {
+        panic!("Escrow already refunded");
+    }
+    escrow
+}
+
+/// Load an unsettled escrow and require its arbiter's auth
+fn require_arbiter(env: &Env, escrow_id: &BytesN<32>) -> (EscrowData, Address) {
+    let escrow = load_unsettled(env, escrow_id);
+    let arbiter = escrow
+        .arbiter
+        .clone()
+        .unwrap_or_else(|| panic_with_error!(env, EscrowError::NoArbiter));
+    arbiter.require_auth();
+    (escrow, arbiter)
+}
+
+/// Send whatever has not been claimed back to the sender and mark the escrow refunded
+/// A keeper fee, if any, is paid out of the unclaimed amount first
+fn return_to_sender(
+    env: &Env,
+    escrow_id: &BytesN<32>,
+    escrow: EscrowData,
+    keeper_fee: Option<(&Address, i128)>,
+) -> EscrowData {
+    // Transfer unclaimed tokens back to sender
+    let remaining = escrow.amount - escrow.claimed_amount;
+    let token_client = token::Client::new(env, &escrow.token);
+    let mut to_sender = remaining;
+    if let Some((keeper, fee)) = keeper_fee.filter(|(_, fee)| *fee > 0) {
+        token_client.transfer(&env.current_contract_address(), keeper, &fee);
+        to_sender -= fee;
+    }
+    token_client.transfer(&env.current_contract_address(), &escrow.sender, &to_sender);
+    mark_refunded(env, escrow_id, escrow)
+}
+
+/// Record that an escrow's unclaimed funds went back to its sender
+fn mark_refunded(env: &Env, escrow_id: &BytesN<32>, mut escrow: EscrowData) -> EscrowData {
+    let remaining = escrow.amount - escrow.claimed_amount;
+    escrow.is_refunded = true;
+    escrow.settled_at = env.ledger().timestamp();
+    adjust_outstanding(env, &escrow.token, -remaining);
+    env.storage()
+        .persistent()
+        .remove(&DataKey::QueuedRefund(escrow_id.clone()));
+    env.storage().persistent().set(&DataKey::Escrow(escrow_id.clone()), &escrow);
+    escrow
+}
+
+/// Refund an expired escrow to its sender, rewarding `keeper` if one triggered it
+fn refund_expired_escrow(env: &Env, escrow_id: &BytesN<32>, keeper: Option<&Address>) {
+    let escrow = load_unsettled(env, escrow_id);
+
+    // Check if expired
+    if !escrow.expiry.is_reached(env) {
+        panic!("Escrow not yet expired");
+    }
+
+    pay_expired_refund(env, escrow_id, escrow, keeper);
+}
+
+/// Refund an escrow already checked to be unsettled and expired
+fn pay_expired_refund(
+    env: &Env,
+    escrow_id: &BytesN<32>,
+    escrow: EscrowData,
+    keeper: Option<&Address>,
+) {
+    let fee_bps: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::KeeperFeeBps)
+        .unwrap_or(0);
+    let fee = (escrow.amount - escrow.claimed_amount) * fee_bps as i128 / 10_000;
+    let escrow = return_to_sender(env, escrow_id, escrow, keeper.map(|k| (k, fee)));
+
+    publish_refunded(env, escrow_id, escrow);
+    if let Some(keeper) = keeper.filter(|_| fee > 0) {
+        env.events().publish(
+            (Symbol::new(env, KEEPER_REWARDED),),
+            (escrow_id.clone(), keeper.clone(), fee),
+        );
+    }
+}
+
+fn publish_refunded(env: &Env, escrow_id: &BytesN<32>, escrow: EscrowData) {
+    env.events().publish(
+        (Symbol::new(env, ESCROW_REFUNDED), escrow_id.clone(), escrow.sender.clone()),
+        EscrowRefunded {
+            escrow_id: escrow_id.clone(),
+            sender: escrow.sender,
+            token: escrow.token,
+            amount: escrow.amount - escrow.claimed_amount,
+            ledger: env.ledger().sequence(),
+        },
+    );
+}
+
+/// Refund an expired escrow for `refund_expired_batch`, leaving it untouched if the
+/// token transfer fails so one bad token cannot revert the whole batch
+fn try_pay_expired_refund(env: &Env, escrow_id: &BytesN<32>, escrow: EscrowData) -> RefundOutcome {
+    let token_client = token::Client::new(env, &escrow.token);
+    let remaining = escrow.amount - escrow.claimed_amount;
+    let transfer =
+        token_client.try_transfer(&env.current_contract_address(), &escrow.sender, &remaining);
+    if !matches!(transfer, Ok(Ok(()))) {
+        return RefundOutcome::Failed;
+    }
+
+    let escrow = mark_refunded(env, escrow_id, escrow);
+    publish_refunded(env, escrow_id, escrow);
+    RefundOutcome::Refunded
+}
+
+/// Return an unsettled escrow to its sender regardless of expiry
+fn force_refund(env: &Env, escrow_id: &BytesN<32>, reason: u32) {
+    let escrow = return_to_sender(env, escrow_id, load_unsettled(env, escrow_id), None);
+
+    // Emit event
+    env.events().publish(
+        (Symbol::new(env, ESCROW_ADMIN_REFUNDED), escrow_id.clone(), escrow.sender.clone()),
+        EscrowAdminRefunded {
+            escrow_id: escrow_id.clone(),
+            sender: escrow.sender,
+            token: escrow.token,
+            amount: escrow.amount - escrow.claimed_amount,
+            reason,
+            ledger: env.ledger().sequence(),
+        },
+    );
+}
+
+fn outstanding(env: &Env, token: &Address) -> i128 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Outstanding(token.clone()))
+        .unwrap_or(0)
+}
+
+/// Tracks the amount owed to open escrows per token
+/// Kept in persistent storage: any token address is accepted, so per-token entries
+/// in instance storage would let anyone grow it
+fn adjust_outstanding(env: &Env, token: &Address, delta: i128) {
+    env.storage().persistent().set(
+        &DataKey::Outstanding(token.clone()),
+        &(outstanding(env, token) + delta),
+    );
+}
 
 #[contract]
 pub struct EscrowContract;
@@ -35,12 +915,52 @@ pub struct EscrowContract;
 impl EscrowContract {
     /// Initialize the contract with an admin address
     pub fn initialize(env: Env, admin: Address) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic_with_error!(&env, EscrowError::AlreadyInitialized);
+        }
         admin.require_auth();
         env.storage().instance().set(&DataKey::Admin, &admin);
     }
 
+    /// Current admin proposes a successor; takes effect once they call `accept_admin`
+    pub fn propose_admin(env: Env, new_admin: Address) {
+        require_admin(&env);
+        env.storage().instance().set(&DataKey::PendingAdmin, &new_admin);
+    }
+
+    /// Proposed admin accepts the role, replacing the current admin
+    pub fn accept_admin(env: Env) {
+        let new_admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::PendingAdmin)
+            .unwrap_or_else(|| panic_with_error!(&env, EscrowError::NoPendingAdmin));
+        new_admin.require_auth();
+
+        let previous: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        env.storage().instance().set(&DataKey::Admin, &new_admin);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+
+        env.events().publish(
+            (Symbol::new(&env, ADMIN_CHANGED),),
+            AdminChanged {
+                previous,
+                admin: new_admin,
+            },
+        );
+    }
+
+    /// Get the current admin
+    pub fn get_admin(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .unwrap_or_else(|| panic!("Admin not set"))
+    }
+
     /// Create a new escrow
-    /// Returns escrow_id
+    /// The supplied id is scoped to the sender (see `scoped_escrow_id`), so another
+    /// account cannot occupy it first. Returns the scoped escrow_id
     pub fn create_escrow(
         env: Env,
         escrow_id: BytesN<32>,
@@ -48,39 +968,205 @@ impl EscrowContract {
         recipient_hash: BytesN<32>,
         token: Address,
         amount: i128,
-        expiry_ledger: u32,
+        expiry: Expiry,
+    ) -> BytesN<32> {
+        Self::create_escrow_with_arbiter(
+            env,
+            escrow_id,
+            sender,
+            recipient_hash,
+            token,
+            amount,
+            expiry,
+            None,
+        )
+    }
+
+    /// Create a new escrow whose release needs the arbiter's `approve_release`
+    /// Same id scoping as `create_escrow`. Returns the scoped escrow_id
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_escrow_with_arbiter(
+        env: Env,
+        escrow_id: BytesN<32>,
+        sender: Address,
+        recipient_hash: BytesN<32>,
+        token: Address,
+        amount: i128,
+        expiry: Expiry,
+        arbiter: Option<Address>,
     ) -> BytesN<32> {
         sender.require_auth();
-        
-        // Check if escrow already exists
-        if env.storage().persistent().has(&DataKey::Escrow(escrow_id.clone())) {
-            panic!("Escrow already exists");
+
+        let escrow_id = scoped_escrow_id(&env, &sender, &escrow_id);
+        let escrow = secret_escrow(sender, recipient_hash, token, amount, expiry, arbiter);
+        store_new_escrow(&env, &escrow_id, &escrow);
+
+        escrow_id
+    }
+
+    /// Create an escrow paid out in milestones: nothing is claimable until the
+    /// arbiter, or the sender if there is none, unlocks tranches with `release_tranche`
+    /// Returns the scoped escrow_id, as `create_escrow`
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_milestone_escrow(
+        env: Env,
+        escrow_id: BytesN<32>,
+        sender: Address,
+        recipient_hash: BytesN<32>,
+        token: Address,
+        amount: i128,
+        expiry: Expiry,
+        arbiter: Option<Address>,
+    ) -> BytesN<32> {
+        sender.require_auth();
+
+        let escrow_id = scoped_escrow_id(&env, &sender, &escrow_id);
+        let mut escrow = secret_escrow(sender, recipient_hash, token, amount, expiry, arbiter);
+        escrow.unlocked_amount = 0;
+        store_new_escrow(&env, &escrow_id, &escrow);
+
+        escrow_id
+    }
+
+    /// Create several secret-claimed escrows in `token` with one transfer of their total
+    /// Each spec is validated and id-scoped as in `create_escrow`; returns the scoped
+    /// escrow_ids in order
+    pub fn create_escrows_batch(
+        env: Env,
+        sender: Address,
+        token: Address,
+        specs: Vec<EscrowSpec>,
+    ) -> Vec<BytesN<32>> {
+        sender.require_auth();
+        if specs.len() > MAX_BATCH {
+            panic_with_error!(&env, EscrowError::BatchTooLarge);
         }
 
-        // Transfer tokens from sender to contract
-        let token_client = token::Client::new(&env, &token);
-        token_client.transfer(&sender, &env.current_contract_address(), &amount);
+        let mut ids = Vec::new(&env);
+        let mut total: i128 = 0;
+        for spec in specs.iter() {
+            let escrow_id = scoped_escrow_id(&env, &sender, &spec.escrow_id);
+            let escrow = secret_escrow(
+                sender.clone(),
+                spec.recipient_hash,
+                token.clone(),
+                spec.amount,
+                spec.expiry,
+                None,
+            );
+            validate_new_escrow(&env, &escrow);
+            total = total
+                .checked_add(escrow.amount)
+                .unwrap_or_else(|| panic!("Batch total overflows"));
+            record_new_escrow(&env, &escrow_id, &escrow);
+            ids.push_back(escrow_id);
+        }
+
+        if total > 0 {
+            let token_client = token::Client::new(&env, &token);
+            token_client.transfer(&sender, &env.current_contract_address(), &total);
+        }
+        ids
+    }
 
-        // Store escrow data
+    /// Create a hash time-locked escrow for cross-chain swaps
+    /// `hashlock` is `sha256(secret)` or `keccak256(secret)` per `hash_algorithm`; only
+    /// `recipient` can claim, by revealing the secret, and the sender can `refund`
+    /// after expiry. Ids are scoped as in `create_escrow`. Returns the scoped escrow_id
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_htlc(
+        env: Env,
+        escrow_id: BytesN<32>,
+        sender: Address,
+        recipient: Address,
+        hashlock: BytesN<32>,
+        hash_algorithm: HashAlgorithm,
+        token: Address,
+        amount: i128,
+        expiry: Expiry,
+    ) -> BytesN<32> {
+        sender.require_auth();
+
+        let escrow_id = scoped_escrow_id(&env, &sender, &escrow_id);
         let escrow = EscrowData {
-            sender,
-            recipient_hash,
-            token,
-            amount,
-            expiry_ledger,
-            is_claimed: false,
-            is_refunded: false,
+            hash_version: RECIPIENT_HASH_VERSION_HTLC,
+            recipient: Some(recipient),
+            hash_algorithm,
+            ..secret_escrow(sender, hashlock, token, amount, expiry, None)
+        };
+        store_new_escrow(&env, &escrow_id, &escrow);
+
+        escrow_id
+    }
+
+    /// Create an escrow claimed with a signature by `claim_key` instead of a secret
+    /// The recipient is chosen when the key holder signs `claim_message`. Ids are
+    /// scoped as in `create_escrow`. Returns the scoped escrow_id
+    pub fn create_escrow_signed(
+        env: Env,
+        escrow_id: BytesN<32>,
+        sender: Address,
+        claim_key: BytesN<32>,
+        token: Address,
+        amount: i128,
+        expiry: Expiry,
+    ) -> BytesN<32> {
+        sender.require_auth();
+
+        let escrow_id = scoped_escrow_id(&env, &sender, &escrow_id);
+        let unused_hash = BytesN::from_array(&env, &[0; 32]);
+        let escrow = EscrowData {
+            hash_version: RECIPIENT_HASH_VERSION_SIGNED,
+            claim_key: Some(claim_key.into()),
+            ..secret_escrow(sender, unused_hash, token, amount, expiry, None)
+        };
+        store_new_escrow(&env, &escrow_id, &escrow);
+
+        escrow_id
+    }
+
+    /// Create a new escrow with a contract-generated id
+    /// `nonce` must equal `next_nonce(sender)`; the hash must come from `recipient_hash_for_nonce`
+    /// Returns escrow_id
+    pub fn create_escrow_auto(
+        env: Env,
+        sender: Address,
+        nonce: u64,
+        recipient_hash: BytesN<32>,
+        token: Address,
+        amount: i128,
+        expiry: Expiry,
+    ) -> BytesN<32> {
+        sender.require_auth();
+
+        if nonce != Self::next_nonce(env.clone(), sender.clone()) {
+            panic!("Invalid nonce");
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::Nonce(sender.clone()), &(nonce + 1));
+
+        let escrow_id = derived_escrow_id(&env, &sender, nonce, &recipient_hash);
+        let escrow = EscrowData {
+            hash_version: RECIPIENT_HASH_VERSION_NONCE,
+            nonce,
+            ..secret_escrow(sender, recipient_hash, token, amount, expiry, None)
         };
-        
-        env.storage().persistent().set(&DataKey::Escrow(escrow_id.clone()), &escrow);
-        
-        // Emit event
-        env.events().publish((ESCROW_CREATED,), (escrow_id.clone(),));
-        
+        store_new_escrow(&env, &escrow_id, &escrow);
+
         escrow_id
     }
 
+    /// Nonce the sender's next `create_escrow_auto` call must use
+    pub fn next_nonce(env: Env, sender: Address) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Nonce(sender))
+            .unwrap_or(0)
+    }
+
     /// Claim escrow funds
+    /// Pays out whatever has been unlocked but not claimed yet
     pub fn claim(
         env: Env,
         escrow_id: BytesN<32>,
@@ -88,142 +1174,331 @@ impl EscrowContract {
         claim_secret: BytesN<32>,
     ) {
         recipient.require_auth();
-        
-        // Get escrow data
-        let mut escrow: EscrowData = env
-            .storage()
+
+        let escrow = load_claimable(&env, &escrow_id, &recipient, &claim_secret);
+        let unclaimed = unclaimed_unlocked(&env, &escrow);
+        reveal_preimage(&env, &escrow_id, &escrow, &recipient, claim_secret);
+        pay_tranche(&env, escrow_id, escrow, recipient, unclaimed);
+    }
+
+    /// Claim a signed-claim escrow with the claim key's signature over `claim_message`
+    /// Pays out everything at once, so each signature can only be used once
+    pub fn claim_signed(
+        env: Env,
+        escrow_id: BytesN<32>,
+        recipient: Address,
+        signature: BytesN<64>,
+    ) {
+        recipient.require_auth();
+
+        let escrow = load_unsettled(&env, &escrow_id);
+        let claim_key: BytesN<32> = match &escrow.claim_key {
+            Some(key) => key.try_into().unwrap(),
+            None => panic!("Escrow does not accept signed claims"),
+        };
+        let message = claim_message(&env, &env.current_contract_address(), &escrow_id, &recipient);
+        env.crypto().ed25519_verify(&claim_key, &message, &signature);
+        require_released(&env, &escrow);
+
+        let unclaimed = unclaimed_unlocked(&env, &escrow);
+        pay_tranche(&env, escrow_id, escrow, recipient, unclaimed);
+    }
+
+    /// Claim part of the unlocked amount, e.g. one milestone's tranche
+    /// The rest stays claimable, or refundable to the sender after expiry
+    pub fn claim_partial(
+        env: Env,
+        escrow_id: BytesN<32>,
+        recipient: Address,
+        claim_secret: BytesN<32>,
+        amount: i128,
+    ) {
+        recipient.require_auth();
+
+        let escrow = load_claimable(&env, &escrow_id, &recipient, &claim_secret);
+        if amount <= 0 || amount > escrow.unlocked_amount - escrow.claimed_amount {
+            panic_with_error!(&env, EscrowError::InvalidClaimAmount);
+        }
+        reveal_preimage(&env, &escrow_id, &escrow, &recipient, claim_secret);
+        pay_tranche(&env, escrow_id, escrow, recipient, amount);
+    }
+
+    /// Unlock `amount` more of a milestone escrow for the recipient to claim
+    /// Authorised by the arbiter if the escrow has one, otherwise by the sender.
+    /// An arbiter's release also approves the escrow for release, as `approve_release`
+    /// does, so the sender can no longer dispute it
+    pub fn release_tranche(env: Env, escrow_id: BytesN<32>, amount: i128) {
+        let mut escrow = load_unsettled(&env, &escrow_id);
+        let releaser = escrow.arbiter.clone().unwrap_or(escrow.sender.clone());
+        releaser.require_auth();
+        if amount <= 0 || amount > escrow.amount - escrow.unlocked_amount {
+            panic_with_error!(&env, EscrowError::InvalidReleaseAmount);
+        }
+
+        if escrow.arbiter.is_some() {
+            escrow.arbiter_status = ArbiterStatus::Released;
+        }
+        escrow.unlocked_amount += amount;
+        env.storage()
             .persistent()
-            .get(&DataKey::Escrow(escrow_id.clone()))
-            .unwrap_or_else(|| panic!("Escrow not found"));
-        
-        // Check if already claimed or refunded
-        if escrow.is_claimed {
-            panic!("Escrow already claimed");
-        }
-        if escrow.is_refunded {
-            panic!("Escrow already refunded");
-        }
-        
-        // Verify recipient hash matches
-        let recipient_bytes = recipient.to_bytes();
-        let secret_bytes = claim_secret.to_bytes();
-        let mut hash_input = Vec::new(&env);
-        for byte in recipient_bytes.iter() {
-            hash_input.push_back(byte);
-        }
-        for byte in secret_bytes.iter() {
-            hash_input.push_back(byte);
-        }
-        let computed_hash = env.crypto().sha256(&hash_input.to_bytes());
-        
-        if computed_hash != escrow.recipient_hash {
-            panic!("Invalid recipient or secret");
-        }
-        
-        // Transfer tokens to recipient
-        let token_client = token::Client::new(&env, &escrow.token);
-        token_client.transfer(
-            &env.current_contract_address(),
-            &recipient,
-            &escrow.amount,
-        );
-        
-        // Mark as claimed
-        escrow.is_claimed = true;
-        env.storage().persistent().set(&DataKey::Escrow(escrow_id.clone()), &escrow);
-        
-        // Emit event
+            .set(&DataKey::Escrow(escrow_id.clone()), &escrow);
         env.events().publish(
-            (ESCROW_CLAIMED,),
-            (escrow_id, recipient, env.ledger().sequence()),
+            (Symbol::new(&env, ESCROW_TRANCHE_RELEASED), escrow_id.clone(), escrow.sender),
+            EscrowTrancheReleased {
+                escrow_id,
+                releaser,
+                amount,
+                unlocked: escrow.unlocked_amount,
+            },
         );
     }
 
     /// Refund escrow to sender (only after expiry)
+    /// Anyone may call it: the funds always go to the sender, so a lost sender key
+    /// does not strand them. Also the fallback for arbitrated escrows whose arbiter
+    /// never decides
     pub fn refund(env: Env, escrow_id: BytesN<32>) {
-        // Get escrow data
-        let mut escrow: EscrowData = env
-            .storage()
+        refund_expired_escrow(&env, &escrow_id, None);
+    }
+
+    /// Refund an expired escrow to its sender on their behalf, paying `keeper` the
+    /// configured keeper fee out of the refunded amount
+    pub fn refund_expired(env: Env, escrow_id: BytesN<32>, keeper: Address) {
+        keeper.require_auth();
+        refund_expired_escrow(&env, &escrow_id, Some(&keeper));
+    }
+
+    /// Refund every expired escrow in `escrow_ids` to its sender
+    /// Ids that cannot be refunded are skipped, as are ids whose token transfer fails;
+    /// returns one outcome per id, in order.
+    /// Archived nonce-derived escrows leave no tombstone, so they report `NotFound`
+    /// rather than `Settled`: the contract cannot tell them from ids never used
+    pub fn refund_expired_batch(env: Env, escrow_ids: Vec<BytesN<32>>) -> Vec<RefundOutcome> {
+        if escrow_ids.len() > MAX_BATCH {
+            panic_with_error!(&env, EscrowError::BatchTooLarge);
+        }
+
+        let mut outcomes = Vec::new(&env);
+        for escrow_id in escrow_ids.iter() {
+            let escrow: Option<EscrowData> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Escrow(escrow_id.clone()));
+            let archived = env
+                .storage()
+                .persistent()
+                .has(&DataKey::Tombstone(escrow_id.clone()));
+            let outcome = match escrow {
+                None if archived => RefundOutcome::Settled,
+                None => RefundOutcome::NotFound,
+                Some(e) if e.is_claimed || e.is_refunded => RefundOutcome::Settled,
+                Some(e) if !e.expiry.is_reached(&env) => RefundOutcome::NotExpired,
+                Some(e) => try_pay_expired_refund(&env, &escrow_id, e),
+            };
+            outcomes.push_back(outcome);
+        }
+        outcomes
+    }
+
+    /// Admin sets the keeper incentive for `refund_expired`, in basis points
+    pub fn set_keeper_fee(env: Env, fee_bps: u32) {
+        require_admin(&env);
+        if fee_bps > MAX_KEEPER_FEE_BPS {
+            panic_with_error!(&env, EscrowError::KeeperFeeTooHigh);
+        }
+        env.storage().instance().set(&DataKey::KeeperFeeBps, &fee_bps);
+    }
+
+    /// Arbiter allows the recipient to claim an arbitrated escrow
+    pub fn approve_release(env: Env, escrow_id: BytesN<32>) {
+        let (mut escrow, arbiter) = require_arbiter(&env, &escrow_id);
+        escrow.arbiter_status = ArbiterStatus::Released;
+        env.storage()
             .persistent()
-            .get(&DataKey::Escrow(escrow_id.clone()))
-            .unwrap_or_else(|| panic!("Escrow not found"));
-        
-        // Sender can refund their own escrow
-        escrow.sender.require_auth();
-        
-        // Check if already claimed or refunded
-        if escrow.is_claimed {
-            panic!("Escrow already claimed");
-        }
-        if escrow.is_refunded {
-            panic!("Escrow already refunded");
-        }
-        
-        // Check if expired
-        if env.ledger().sequence() < escrow.expiry_ledger {
-            panic!("Escrow not yet expired");
-        }
-        
-        // Transfer tokens back to sender
-        let token_client = token::Client::new(&env, &escrow.token);
-        token_client.transfer(
-            &env.current_contract_address(),
-            &escrow.sender.clone(),
-            &escrow.amount,
+            .set(&DataKey::Escrow(escrow_id.clone()), &escrow);
+        env.events().publish(
+            (Symbol::new(&env, ESCROW_RELEASE_APPROVED),),
+            (escrow_id, arbiter),
         );
-        
-        // Mark as refunded
-        escrow.is_refunded = true;
-        env.storage().persistent().set(&DataKey::Escrow(escrow_id.clone()), &escrow);
-        
-        // Emit event
+    }
+
+    /// Arbiter returns an arbitrated escrow to its sender, before or after expiry
+    pub fn approve_refund(env: Env, escrow_id: BytesN<32>) {
+        let (escrow, arbiter) = require_arbiter(&env, &escrow_id);
+        let escrow = return_to_sender(&env, &escrow_id, escrow, None);
         env.events().publish(
-            (ESCROW_REFUNDED,),
-            (escrow_id, escrow.sender, env.ledger().sequence()),
+            (Symbol::new(&env, ESCROW_ARBITER_REFUNDED), escrow_id.clone(), escrow.sender.clone()),
+            EscrowArbiterRefunded {
+                escrow_id,
+                sender: escrow.sender,
+                arbiter,
+                token: escrow.token,
+                amount: escrow.amount - escrow.claimed_amount,
+                ledger: env.ledger().sequence(),
+            },
         );
     }
 
-    /// Admin can force refund (emergency)
-    pub fn admin_refund(env: Env, escrow_id: BytesN<32>) {
+    /// Sender disputes an arbitrated escrow still awaiting the arbiter's decision
+    /// The arbiter settles it with `approve_release` or `approve_refund`; if they
+    /// stay silent the sender can `refund` after expiry
+    pub fn dispute(env: Env, escrow_id: BytesN<32>) {
+        let mut escrow = load_unsettled(&env, &escrow_id);
+        escrow.sender.require_auth();
+        if escrow.arbiter.is_none() {
+            panic_with_error!(&env, EscrowError::NoArbiter);
+        }
+        if escrow.arbiter_status == ArbiterStatus::Released {
+            panic_with_error!(&env, EscrowError::AlreadyReleased);
+        }
+
+        escrow.arbiter_status = ArbiterStatus::Disputed;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Escrow(escrow_id.clone()), &escrow);
+        env.events().publish(
+            (Symbol::new(&env, ESCROW_DISPUTED),),
+            (escrow_id, escrow.sender),
+        );
+    }
+
+    /// Admin can force refund (emergency), queued behind the refund delay
+    /// Once guardians are configured, forced refunds go through `propose_refund` instead
+    pub fn admin_refund(env: Env, escrow_id: BytesN<32>, reason: u32) {
         // Check admin auth
-        let admin: Address = env
+        require_admin(&env);
+        if guardians::get_set(&env).is_some() {
+            panic_with_error!(&env, EscrowError::GuardianApprovalRequired);
+        }
+
+        queue_refund(&env, &escrow_id, reason);
+    }
+
+    /// Execute a queued forced refund once its delay has passed
+    /// Already authorised by the admin or guardians, so anyone may call it
+    pub fn execute_admin_refund(env: Env, escrow_id: BytesN<32>) {
+        let key = DataKey::QueuedRefund(escrow_id.clone());
+        let queued: QueuedRefund = env
             .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .unwrap_or_else(|| panic!("Admin not set"));
-        admin.require_auth();
-        
-        // Get escrow data
-        let mut escrow: EscrowData = env
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| panic_with_error!(&env, EscrowError::RefundNotQueued));
+        if env.ledger().timestamp() < queued.executable_at {
+            panic_with_error!(&env, EscrowError::TimelockActive);
+        }
+
+        env.storage().persistent().remove(&key);
+        force_refund(&env, &escrow_id, queued.reason);
+    }
+
+    /// Get the queued forced refund for an escrow, if any
+    pub fn get_queued_refund(env: Env, escrow_id: BytesN<32>) -> Option<QueuedRefund> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::QueuedRefund(escrow_id))
+    }
+
+    /// Admin sets how long queued forced refunds wait before executing
+    /// Must be at least `MIN_REFUND_DELAY`
+    pub fn set_refund_delay(env: Env, delay: u64) {
+        require_admin(&env);
+        if delay < MIN_REFUND_DELAY {
+            panic_with_error!(&env, EscrowError::RefundDelayTooShort);
+        }
+        env.storage().instance().set(&DataKey::RefundDelay, &delay);
+    }
+
+    /// Get how long queued forced refunds wait before executing
+    pub fn get_refund_delay(env: Env) -> u64 {
+        refund_delay(&env)
+    }
+
+    /// Admin sets the guardian set that must approve forced refunds
+    /// Once a set is configured, replacing it also needs auth from a threshold of
+    /// the current guardians in `approvers`; the first set needs none
+    pub fn set_guardians(env: Env, set: GuardianSet, approvers: Vec<Address>) {
+        require_admin(&env);
+        guardians::validate_set(&env, &set);
+        if let Some(current) = guardians::get_set(&env) {
+            guardians::require_quorum(&env, &current, &approvers);
+        }
+        env.storage().instance().set(&DataKey::Guardians, &set);
+    }
+
+    /// Get the guardian set, if configured
+    pub fn get_guardians(env: Env) -> Option<GuardianSet> {
+        guardians::get_set(&env)
+    }
+
+    /// A guardian opens a forced refund proposal, counting as its first approval
+    /// Returns proposal_id
+    pub fn propose_refund(env: Env, guardian: Address, escrow_id: BytesN<32>, reason: u32) -> u64 {
+        let set = guardians::require_guardian(&env, &guardian);
+        if !env.storage().persistent().has(&DataKey::Escrow(escrow_id.clone())) {
+            panic!("Escrow not found");
+        }
+
+        let proposal_id: u64 = env
             .storage()
+            .instance()
+            .get(&DataKey::NextProposalId)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::NextProposalId, &(proposal_id + 1));
+
+        let expires_at = env.ledger().timestamp().saturating_add(set.proposal_ttl);
+        let proposal = RefundProposal {
+            escrow_id: escrow_id.clone(),
+            proposer: guardian.clone(),
+            reason,
+            approvals: Vec::new(&env),
+            expires_at,
+            executed: false,
+        };
+        env.storage()
             .persistent()
-            .get(&DataKey::Escrow(escrow_id.clone()))
-            .unwrap_or_else(|| panic!("Escrow not found"));
-        
-        if escrow.is_claimed {
-            panic!("Escrow already claimed");
-        }
-        if escrow.is_refunded {
-            panic!("Escrow already refunded");
-        }
-        
-        // Transfer tokens back to sender
-        let token_client = token::Client::new(&env, &escrow.token);
-        token_client.transfer(
-            &env.current_contract_address(),
-            &escrow.sender.clone(),
-            &escrow.amount,
-        );
-        
-        // Mark as refunded
-        escrow.is_refunded = true;
-        env.storage().persistent().set(&DataKey::Escrow(escrow_id.clone()), &escrow);
-        
-        // Emit event
+            .set(&DataKey::RefundProposal(proposal_id), &proposal);
         env.events().publish(
-            (ESCROW_REFUNDED,),
-            (escrow_id, escrow.sender, env.ledger().sequence()),
+            (Symbol::new(&env, REFUND_PROPOSED), proposal_id),
+            (escrow_id, guardian.clone(), reason, expires_at),
         );
+
+        record_approval(&env, &set, guardian, proposal_id);
+        proposal_id
+    }
+
+    /// A guardian approves a proposal; the refund is queued once the threshold is met
+    pub fn approve_refund_proposal(env: Env, guardian: Address, proposal_id: u64) {
+        let set = guardians::require_guardian(&env, &guardian);
+        record_approval(&env, &set, guardian, proposal_id);
+    }
+
+    /// Get a forced refund proposal
+    pub fn get_refund_proposal(env: Env, proposal_id: u64) -> RefundProposal {
+        guardians::load_proposal(&env, proposal_id)
+    }
+
+    /// Admin sets how far in the future a new escrow's expiry may be
+    pub fn set_expiry_window(env: Env, window: ExpiryWindow) {
+        require_admin(&env);
+        if window.min_ledgers > window.max_ledgers || window.min_secs > window.max_secs {
+            panic_with_error!(&env, EscrowError::InvalidExpiryWindow);
+        }
+        env.storage().instance().set(&DataKey::ExpiryWindow, &window);
+    }
+
+    /// Get the expiry window new escrows are checked against
+    pub fn get_expiry_window(env: Env) -> ExpiryWindow {
+        expiry_window(&env)
+    }
+
+    /// Admin bans (or unbans) a token from being used for new escrows
+    pub fn set_token_banned(env: Env, token: Address, banned: bool) {
+        require_admin(&env);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Banned(token), &banned);
     }
 
     /// Get escrow details
@@ -233,4 +1508,122 @@ impl EscrowContract {
             .get(&DataKey::Escrow(escrow_id))
             .unwrap_or_else(|| panic!("Escrow not found"))
     }
-}
\ No newline at end of file
+
+    /// Number of escrows ever created by `sender`
+    pub fn count_escrows_by_sender(env: Env, sender: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::SenderCount(sender))
+            .unwrap_or(0)
+    }
+
+    /// Escrows created by `sender`, oldest first, from index slots `start..start + limit`
+    /// Archived escrows are left out
+    /// With `status` set, only matching escrows in that range are returned, so a page
+    /// may be short; continue from `start + limit` until `count_escrows_by_sender`
+    pub fn list_escrows_by_sender(
+        env: Env,
+        sender: Address,
+        start: u32,
+        limit: u32,
+        status: Option<EscrowStatus>,
+    ) -> Vec<EscrowEntry> {
+        let n = Self::count_escrows_by_sender(env.clone(), sender.clone());
+        let end = start.saturating_add(limit.min(MAX_PAGE)).min(n);
+        let mut out = Vec::new(&env);
+        for i in start..end {
+            let escrow_id: BytesN<32> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::SenderAt(sender.clone(), i))
+                .unwrap();
+            // Archived escrows are no longer listed
+            let Some(escrow) = env
+                .storage()
+                .persistent()
+                .get::<DataKey, EscrowData>(&DataKey::Escrow(escrow_id.clone()))
+            else {
+                continue;
+            };
+            let current = escrow_status(&env, &escrow);
+            if status.is_none_or(|s| s == current) {
+                out.push_back(EscrowEntry {
+                    escrow_id,
+                    status: current,
+                    escrow,
+                });
+            }
+        }
+        out
+    }
+
+    /// Drop a settled escrow's data once the archive grace period has passed
+    /// Escrows with caller-supplied ids leave a `Tombstone` so the id cannot be reused;
+    /// nonce-derived ids can never recur, so those are removed outright. Anyone may call it
+    pub fn archive(env: Env, escrow_id: BytesN<32>) {
+        let key = DataKey::Escrow(escrow_id.clone());
+        let escrow: EscrowData = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| panic!("Escrow not found"));
+        let status = escrow_status(&env, &escrow);
+        if status != EscrowStatus::Claimed && status != EscrowStatus::Refunded {
+            panic_with_error!(&env, EscrowError::NotSettled);
+        }
+        let grace: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ArchiveGrace)
+            .unwrap_or(DEFAULT_ARCHIVE_GRACE);
+        if env.ledger().timestamp() < escrow.settled_at.saturating_add(grace) {
+            panic_with_error!(&env, EscrowError::GracePeriodActive);
+        }
+
+        env.storage().persistent().remove(&key);
+        let tombstoned = escrow.hash_version != RECIPIENT_HASH_VERSION_NONCE;
+        if tombstoned {
+            env.storage().persistent().set(
+                &DataKey::Tombstone(escrow_id.clone()),
+                &Tombstone {
+                    sender: escrow.sender,
+                    status,
+                    settled_at: escrow.settled_at,
+                },
+            );
+        }
+        env.events().publish(
+            (Symbol::new(&env, ESCROW_ARCHIVED),),
+            (escrow_id, tombstoned),
+        );
+    }
+
+    /// Get the tombstone left by archiving an escrow, if any
+    pub fn get_tombstone(env: Env, escrow_id: BytesN<32>) -> Option<Tombstone> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Tombstone(escrow_id))
+    }
+
+    /// Admin sets how long settled escrows are kept before they can be archived
+    pub fn set_archive_grace(env: Env, grace: u64) {
+        require_admin(&env);
+        env.storage().instance().set(&DataKey::ArchiveGrace, &grace);
+    }
+
+    /// Compare outstanding escrow amounts for each of `tokens` against the contract's balance
+    pub fn solvency(env: Env, tokens: Vec<Address>) -> Vec<SolvencyReport> {
+        let mut reports = Vec::new(&env);
+        for token in tokens.iter() {
+            let liabilities = outstanding(&env, &token);
+            let balance = token::Client::new(&env, &token).balance(&env.current_contract_address());
+            reports.push_back(SolvencyReport {
+                token,
+                liabilities,
+                balance,
+                shortfall: (liabilities - balance).max(0),
+            });
+        }
+        reports
+    }
+}
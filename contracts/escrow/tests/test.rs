@@ -1,10 +1,18 @@
 #![cfg(test)]
 
+use ed25519_dalek::{Signer, SigningKey};
+use escrow::{
+    claim_message, derived_escrow_id, recipient_hash, recipient_hash_for_nonce, scoped_escrow_id,
+    AdminChanged, ArbiterStatus, EscrowAdminRefunded, EscrowArbiterRefunded, EscrowClaimed,
+    EscrowContract, EscrowContractClient, EscrowCreated, EscrowError, EscrowRefunded, EscrowSpec,
+    EscrowStatus, EscrowTrancheClaimed, EscrowTrancheReleased, Expiry, ExpiryWindow, GuardianSet,
+    HashAlgorithm, RefundOutcome, DEFAULT_ARCHIVE_GRACE, DEFAULT_REFUND_DELAY, MAX_BATCH,
+    MAX_KEEPER_FEE_BPS, MIN_REFUND_DELAY,
+};
 use soroban_sdk::{
-    testutils::{Address as _, Ledger},
-    token, Address, BytesN, Env,
+    testutils::{Address as _, Events, IssuerFlags, Ledger},
+    token, Address, Bytes, BytesN, Env, FromVal, IntoVal, Symbol,
 };
-use escrow::{EscrowContract, EscrowContractClient};
 
 #[test]
 fn test_create_and_claim_escrow() {
@@ -13,7 +21,9 @@ fn test_create_and_claim_escrow() {
 
     // Deploy token contract
     let token_admin = Address::generate(&env);
-    let token_address = env.register_stellar_asset_contract(token_admin.clone());
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
     let token_client = token::Client::new(&env, &token_address);
     
     // Setup accounts
@@ -32,30 +42,23 @@ fn test_create_and_claim_escrow() {
     escrow_client.initialize(&admin);
     
     // Create escrow
-    let escrow_id = BytesN::from_array(&env, &[1u8; 32]);
+    let supplied_id = BytesN::from_array(&env, &[1u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
     let claim_secret = BytesN::from_array(&env, &[2u8; 32]);
     
-    // Calculate recipient hash (simplified for test)
-    let mut hash_input = soroban_sdk::Vec::new(&env);
-    for byte in recipient.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    for byte in claim_secret.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    let recipient_hash = env.crypto().sha256(&hash_input.to_bytes());
+    let recipient_hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
     
     let amount = 500i128;
     let expiry_ledger = env.ledger().sequence() + 1000;
     
     // Create escrow
     let returned_id = escrow_client.create_escrow(
-        &escrow_id,
+        &supplied_id,
         &sender,
         &recipient_hash,
         &token_address,
         &amount,
-        &expiry_ledger,
+        &Expiry::Ledger(expiry_ledger),
     );
     
     assert_eq!(returned_id, escrow_id);
@@ -82,7 +85,9 @@ fn test_refund_after_expiry() {
 
     // Deploy token contract
     let token_admin = Address::generate(&env);
-    let token_address = env.register_stellar_asset_contract(token_admin.clone());
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
     let token_client = token::Client::new(&env, &token_address);
     
     // Setup accounts
@@ -101,28 +106,22 @@ fn test_refund_after_expiry() {
     escrow_client.initialize(&admin);
     
     // Create escrow with short expiry
-    let escrow_id = BytesN::from_array(&env, &[3u8; 32]);
+    let supplied_id = BytesN::from_array(&env, &[3u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
     let claim_secret = BytesN::from_array(&env, &[4u8; 32]);
     
-    let mut hash_input = soroban_sdk::Vec::new(&env);
-    for byte in recipient.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    for byte in claim_secret.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    let recipient_hash = env.crypto().sha256(&hash_input.to_bytes());
+    let recipient_hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
     
     let amount = 300i128;
     let expiry_ledger = env.ledger().sequence() + 10;
     
     escrow_client.create_escrow(
-        &escrow_id,
+        &supplied_id,
         &sender,
         &recipient_hash,
         &token_address,
         &amount,
-        &expiry_ledger,
+        &Expiry::Ledger(expiry_ledger),
     );
     
     // Check sender balance decreased
@@ -153,7 +152,9 @@ fn test_cannot_refund_before_expiry() {
 
     // Deploy token contract
     let token_admin = Address::generate(&env);
-    let token_address = env.register_stellar_asset_contract(token_admin.clone());
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
     
     // Setup accounts
     let admin = Address::generate(&env);
@@ -171,28 +172,22 @@ fn test_cannot_refund_before_expiry() {
     escrow_client.initialize(&admin);
     
     // Create escrow with future expiry
-    let escrow_id = BytesN::from_array(&env, &[5u8; 32]);
+    let supplied_id = BytesN::from_array(&env, &[5u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
     let claim_secret = BytesN::from_array(&env, &[6u8; 32]);
     
-    let mut hash_input = soroban_sdk::Vec::new(&env);
-    for byte in recipient.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    for byte in claim_secret.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    let recipient_hash = env.crypto().sha256(&hash_input.to_bytes());
+    let recipient_hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
     
     let amount = 300i128;
     let expiry_ledger = env.ledger().sequence() + 1000;
     
     escrow_client.create_escrow(
-        &escrow_id,
+        &supplied_id,
         &sender,
         &recipient_hash,
         &token_address,
         &amount,
-        &expiry_ledger,
+        &Expiry::Ledger(expiry_ledger),
     );
     
     // Try to refund before expiry (should panic)
@@ -207,7 +202,9 @@ fn test_cannot_refund_claimed_escrow() {
 
     // Deploy token contract
     let token_admin = Address::generate(&env);
-    let token_address = env.register_stellar_asset_contract(token_admin.clone());
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
     
     // Setup accounts
     let admin = Address::generate(&env);
@@ -225,28 +222,22 @@ fn test_cannot_refund_claimed_escrow() {
     escrow_client.initialize(&admin);
     
     // Create escrow
-    let escrow_id = BytesN::from_array(&env, &[7u8; 32]);
+    let supplied_id = BytesN::from_array(&env, &[7u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
     let claim_secret = BytesN::from_array(&env, &[8u8; 32]);
     
-    let mut hash_input = soroban_sdk::Vec::new(&env);
-    for byte in recipient.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    for byte in claim_secret.to_bytes().iter() {
-        hash_input.push_back(byte);
-    }
-    let recipient_hash = env.crypto().sha256(&hash_input.to_bytes());
+    let recipient_hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
     
     let amount = 300i128;
     let expiry_ledger = env.ledger().sequence() + 100;
     
     escrow_client.create_escrow(
-        &escrow_id,
+        &supplied_id,
         &sender,
         &recipient_hash,
         &token_address,
         &amount,
-        &expiry_ledger,
+        &Expiry::Ledger(expiry_ledger),
     );
     
     // Claim the escrow
@@ -259,4 +250,1685 @@ fn test_cannot_refund_claimed_escrow() {
     
     // Try to refund already claimed escrow (should panic)
     escrow_client.refund(&escrow_id);
-}
\ No newline at end of file
+}
+
+/// Value in `0..bound` for the `n`th draw of `seed`: the leading bytes of
+/// sha256(seed || n), so a failing seed replays the same operations
+fn draw(env: &Env, seed: u64, n: u32, bound: u64) -> u64 {
+    let mut input = [0u8; 12];
+    input[..8].copy_from_slice(&seed.to_be_bytes());
+    input[8..].copy_from_slice(&n.to_be_bytes());
+    let digest: BytesN<32> = env.crypto().sha256(&Bytes::from_array(env, &input)).into();
+    let mut lead = [0u8; 8];
+    lead.copy_from_slice(&digest.to_array()[..8]);
+    u64::from_be_bytes(lead) % bound
+}
+
+fn assert_solvent(escrow_client: &EscrowContractClient, token: &Address) {
+    let reports = escrow_client.solvency(&soroban_sdk::vec![&escrow_client.env, token.clone()]);
+    assert_eq!(reports.len(), 1);
+    for report in reports.iter() {
+        assert_eq!(report.shortfall, 0);
+        assert_eq!(report.liabilities, report.balance);
+    }
+}
+
+#[test]
+fn test_solvency_holds_after_random_operations() {
+    // Every path that moves tokens out of the contract, each settling `Outstanding` its own way
+    const PATHS: usize = 7;
+    let mut exercised = [false; PATHS];
+
+    for seed in 1..=6u64 {
+        let (env, escrow_contract, token_address, admin, sender, recipient) = setup();
+        // Draws hash outside any invocation, so their cost would pile up across the run
+        env.budget().reset_unlimited();
+        let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+        let tokens = [
+            token_address,
+            env.register_stellar_asset_contract_v2(admin).address(),
+        ];
+        for t in tokens.iter() {
+            token::StellarAssetClient::new(&env, t).mint(&sender, &1_000_000);
+        }
+        let arbiter = Address::generate(&env);
+        let keeper = Address::generate(&env);
+        escrow_client.set_keeper_fee(&250);
+        escrow_client.set_refund_delay(&MIN_REFUND_DELAY);
+
+        let claim_secret = BytesN::from_array(&env, &[9u8; 32]);
+        let mut draws = 0u32;
+        let mut next = |bound: u64| {
+            draws += 1;
+            draw(&env, seed, draws, bound)
+        };
+        let mut ids = soroban_sdk::Vec::<BytesN<32>>::new(&env);
+
+        for step in 0..60u8 {
+            match next(10) {
+                0 | 1 => {
+                    let supplied_id = BytesN::from_array(&env, &[step + 1; 32]);
+                    let id = scoped_escrow_id(&env, &sender, &supplied_id);
+                    let hash = recipient_hash(&env, &escrow_contract, &id, &recipient, &claim_secret);
+                    let t = &tokens[next(2) as usize];
+                    let expiry = Expiry::Ledger(env.ledger().sequence() + 1 + next(20) as u32);
+                    let amount = 1 + next(1_000) as i128;
+                    let arbiter = (next(3) == 0).then(|| arbiter.clone());
+                    escrow_client.create_escrow_with_arbiter(
+                        &supplied_id,
+                        &sender,
+                        &hash,
+                        t,
+                        &amount,
+                        &expiry,
+                        &arbiter,
+                    );
+                    ids.push_back(id);
+                }
+                op @ 2..=8 if !ids.is_empty() => {
+                    let id = ids.get_unchecked(next(ids.len() as u64) as u32);
+                    let ok = match op {
+                        2 => escrow_client.try_claim(&id, &recipient, &claim_secret).is_ok(),
+                        3 => {
+                            let amount = 1 + next(300) as i128;
+                            escrow_client
+                                .try_claim_partial(&id, &recipient, &claim_secret, &amount)
+                                .is_ok()
+                        }
+                        4 => escrow_client.try_refund(&id).is_ok(),
+                        5 => escrow_client.try_refund_expired(&id, &keeper).is_ok(),
+                        6 => escrow_client.try_approve_refund(&id).is_ok(),
+                        7 => {
+                            // Let the arbiter unblock claims on arbitrated escrows
+                            let _ = escrow_client.try_approve_release(&id);
+                            escrow_client.try_execute_admin_refund(&id).is_ok()
+                        }
+                        _ => escrow_client.try_admin_refund(&id, &1).is_ok(),
+                    };
+                    exercised[(op - 2) as usize] |= ok;
+                }
+                _ => env.ledger().with_mut(|li| {
+                    li.sequence_number += 5;
+                    li.timestamp += MIN_REFUND_DELAY / 2;
+                }),
+            }
+            for t in tokens.iter() {
+                assert_solvent(&escrow_client, t);
+            }
+        }
+
+        // Nothing is created or lost: every token is with a party or still escrowed
+        for (t, minted) in tokens.iter().zip([1_001_000, 1_000_000]) {
+            let token_client = token::Client::new(&env, t);
+            let held: i128 = [&sender, &recipient, &keeper, &escrow_contract]
+                .iter()
+                .map(|who| token_client.balance(who))
+                .sum();
+            assert_eq!(held, minted);
+        }
+    }
+    assert_eq!(exercised, [true; PATHS]);
+}
+
+#[test]
+fn test_solvency_reports_shortfall() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 400);
+
+    // Move tokens out of the contract without settling any escrow
+    token::Client::new(&env, &token_address).transfer(&escrow_contract, &recipient, &150);
+
+    let report = escrow_client
+        .solvency(&soroban_sdk::vec![&env, token_address.clone()])
+        .get(0)
+        .unwrap();
+    assert_eq!(
+        (report.liabilities, report.balance, report.shortfall),
+        (400, 250, 150)
+    );
+}
+
+#[test]
+fn test_recipient_hash_is_bound_to_escrow_and_contract() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let token_admin = Address::generate(&env);
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &1000);
+
+    let escrow_contract = env.register_contract(None, EscrowContract);
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    escrow_client.initialize(&admin);
+    let other_contract = env.register_contract(None, EscrowContract);
+
+    let claim_secret = BytesN::from_array(&env, &[2u8; 32]);
+    let salts = [10u8, 11, 12].map(|b| BytesN::from_array(&env, &[b; 32]));
+    let [id_a, id_b, id_c] = salts.clone().map(|salt| scoped_escrow_id(&env, &sender, &salt));
+    let expiry_ledger = env.ledger().sequence() + 100;
+
+    // Same preimage, different escrow id
+    let hash_a = recipient_hash(&env, &escrow_contract, &id_a, &recipient, &claim_secret);
+    assert_ne!(
+        hash_a,
+        recipient_hash(&env, &escrow_contract, &id_b, &recipient, &claim_secret)
+    );
+    escrow_client.create_escrow(&salts[1], &sender, &hash_a, &token_address, &100, &Expiry::Ledger(expiry_ledger));
+    assert!(escrow_client
+        .try_claim(&id_b, &recipient, &claim_secret)
+        .is_err());
+
+    // Same preimage, hash computed for another contract
+    let foreign = recipient_hash(&env, &other_contract, &id_c, &recipient, &claim_secret);
+    escrow_client.create_escrow(&salts[2], &sender, &foreign, &token_address, &100, &Expiry::Ledger(expiry_ledger));
+    assert!(escrow_client
+        .try_claim(&id_c, &recipient, &claim_secret)
+        .is_err());
+
+    escrow_client.create_escrow(&salts[0], &sender, &hash_a, &token_address, &100, &Expiry::Ledger(expiry_ledger));
+    escrow_client.claim(&id_a, &recipient, &claim_secret);
+    assert_eq!(escrow_client.get_escrow(&id_a).hash_version, 1);
+}
+
+#[test]
+fn test_supplied_id_cannot_be_squatted() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let token_admin = Address::generate(&env);
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &1000);
+    token::StellarAssetClient::new(&env, &token_address).mint(&attacker, &1000);
+
+    let escrow_contract = env.register_contract(None, EscrowContract);
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    escrow_client.initialize(&admin);
+
+    let supplied_id = BytesN::from_array(&env, &[42u8; 32]);
+    let claim_secret = BytesN::from_array(&env, &[2u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
+    let recipient_hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
+    let expiry_ledger = env.ledger().sequence() + 100;
+
+    // Attacker front-runs with the same supplied id
+    let squatted = escrow_client.create_escrow(
+        &supplied_id,
+        &attacker,
+        &recipient_hash,
+        &token_address,
+        &1,
+        &Expiry::Ledger(expiry_ledger),
+    );
+    assert_ne!(squatted, escrow_id);
+
+    let returned_id = escrow_client.create_escrow(
+        &supplied_id,
+        &sender,
+        &recipient_hash,
+        &token_address,
+        &500,
+        &Expiry::Ledger(expiry_ledger),
+    );
+    assert_eq!(returned_id, escrow_id);
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(token::Client::new(&env, &token_address).balance(&recipient), 500);
+}
+
+#[test]
+fn test_create_escrow_auto_derives_id_from_nonce() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let token_admin = Address::generate(&env);
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let token_client = token::Client::new(&env, &token_address);
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &1000);
+
+    let escrow_contract = env.register_contract(None, EscrowContract);
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    escrow_client.initialize(&admin);
+
+    let claim_secret = BytesN::from_array(&env, &[7u8; 32]);
+    let expiry_ledger = env.ledger().sequence() + 100;
+    assert_eq!(escrow_client.next_nonce(&sender), 0);
+
+    let hash_0 =
+        recipient_hash_for_nonce(&env, &escrow_contract, &sender, 0, &recipient, &claim_secret);
+    let id_0 = escrow_client.create_escrow_auto(
+        &sender,
+        &0,
+        &hash_0,
+        &token_address,
+        &300,
+        &Expiry::Ledger(expiry_ledger),
+    );
+    assert_eq!(id_0, derived_escrow_id(&env, &sender, 0, &hash_0));
+    assert_eq!(escrow_client.next_nonce(&sender), 1);
+
+    // Reusing a nonce is rejected
+    assert!(escrow_client
+        .try_create_escrow_auto(&sender, &0, &hash_0, &token_address, &300, &Expiry::Ledger(expiry_ledger))
+        .is_err());
+
+    let hash_1 =
+        recipient_hash_for_nonce(&env, &escrow_contract, &sender, 1, &recipient, &claim_secret);
+    let id_1 = escrow_client.create_escrow_auto(
+        &sender,
+        &1,
+        &hash_1,
+        &token_address,
+        &200,
+        &Expiry::Ledger(expiry_ledger),
+    );
+    assert_ne!(id_0, id_1);
+
+    escrow_client.claim(&id_0, &recipient, &claim_secret);
+    escrow_client.claim(&id_1, &recipient, &claim_secret);
+    assert_eq!(token_client.balance(&recipient), 500);
+}
+
+#[test]
+fn test_timestamp_expiry_refund() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 1_700_000_000);
+
+    let token_admin = Address::generate(&env);
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let token_client = token::Client::new(&env, &token_address);
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &1000);
+
+    let escrow_contract = env.register_contract(None, EscrowContract);
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    escrow_client.initialize(&admin);
+
+    let supplied_id = BytesN::from_array(&env, &[1u8; 32]);
+    let claim_secret = BytesN::from_array(&env, &[2u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
+    let recipient_hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
+    let expiry_ts = env.ledger().timestamp() + 3600;
+
+    escrow_client.create_escrow(
+        &supplied_id,
+        &sender,
+        &recipient_hash,
+        &token_address,
+        &300,
+        &Expiry::Timestamp(expiry_ts),
+    );
+    assert_eq!(
+        escrow_client.get_escrow(&escrow_id).expiry,
+        Expiry::Timestamp(expiry_ts)
+    );
+
+    // Many ledgers pass but the clock has not reached the expiry
+    env.ledger().with_mut(|li| {
+        li.sequence_number += 1_000;
+        li.timestamp = expiry_ts - 1;
+    });
+    assert!(escrow_client.try_refund(&escrow_id).is_err());
+
+    env.ledger().with_mut(|li| li.timestamp = expiry_ts);
+    escrow_client.refund(&escrow_id);
+    assert_eq!(token_client.balance(&sender), 1000);
+}
+
+/// Env with an initialized escrow contract and a funded sender:
+/// (env, escrow_contract, token_address, admin, sender, recipient)
+fn setup() -> (Env, Address, Address, Address, Address, Address) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let token_admin = Address::generate(&env);
+    let token_address = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &1000);
+
+    let escrow_contract = env.register_contract(None, EscrowContract);
+    EscrowContractClient::new(&env, &escrow_contract).initialize(&admin);
+
+    (env, escrow_contract, token_address, admin, sender, recipient)
+}
+
+#[test]
+fn test_create_escrow_validation() {
+    let (env, escrow_contract, token_address, _admin, sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    env.ledger().with_mut(|li| {
+        li.sequence_number = 100;
+        li.timestamp = 1_000_000;
+    });
+
+    let supplied_id = BytesN::from_array(&env, &[1u8; 32]);
+    let recipient_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let create = |amount: i128, expiry: Expiry| {
+        escrow_client
+            .try_create_escrow(&supplied_id, &sender, &recipient_hash, &token_address, &amount, &expiry)
+            .err()
+            .map(|e| e.unwrap())
+    };
+
+    let err = |e: EscrowError| Some(soroban_sdk::Error::from(e));
+    assert_eq!(create(0, Expiry::Ledger(200)), err(EscrowError::NonPositiveAmount));
+    assert_eq!(create(-5, Expiry::Ledger(200)), err(EscrowError::NonPositiveAmount));
+    assert_eq!(create(10, Expiry::Ledger(99)), err(EscrowError::ExpiryTooSoon));
+    assert_eq!(create(10, Expiry::Ledger(100)), err(EscrowError::ExpiryTooSoon));
+    assert_eq!(create(10, Expiry::Timestamp(999_999)), err(EscrowError::ExpiryTooSoon));
+    assert_eq!(create(10, Expiry::Ledger(101)), None);
+}
+
+#[test]
+fn test_expiry_window_and_banned_token() {
+    let (env, escrow_contract, token_address, _admin, sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    env.ledger().with_mut(|li| {
+        li.sequence_number = 100;
+        li.timestamp = 1_000_000;
+    });
+
+    escrow_client.set_expiry_window(&ExpiryWindow {
+        min_ledgers: 10,
+        max_ledgers: 1_000,
+        min_secs: 60,
+        max_secs: 86_400,
+    });
+    assert_eq!(escrow_client.get_expiry_window().max_secs, 86_400);
+
+    let res = escrow_client.try_set_expiry_window(&ExpiryWindow {
+        min_ledgers: 1_000,
+        max_ledgers: 10,
+        min_secs: 60,
+        max_secs: 86_400,
+    });
+    assert_eq!(
+        res.err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::InvalidExpiryWindow)
+    );
+    assert_eq!(escrow_client.get_expiry_window().max_ledgers, 1_000);
+
+    let recipient_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let mut n = 0u8;
+    let mut create = |expiry: Expiry| {
+        n += 1;
+        escrow_client
+            .try_create_escrow(
+                &BytesN::from_array(&env, &[n; 32]),
+                &sender,
+                &recipient_hash,
+                &token_address,
+                &10,
+                &expiry,
+            )
+            .err()
+            .map(|e| e.unwrap())
+    };
+
+    let err = |e: EscrowError| Some(soroban_sdk::Error::from(e));
+    assert_eq!(create(Expiry::Ledger(105)), err(EscrowError::ExpiryTooSoon));
+    assert_eq!(create(Expiry::Ledger(1_101)), err(EscrowError::ExpiryTooFar));
+    assert_eq!(create(Expiry::Timestamp(1_000_030)), err(EscrowError::ExpiryTooSoon));
+    assert_eq!(create(Expiry::Timestamp(1_100_000)), err(EscrowError::ExpiryTooFar));
+    assert_eq!(create(Expiry::Ledger(1_100)), None);
+    assert_eq!(create(Expiry::Timestamp(1_000_060)), None);
+
+    escrow_client.set_token_banned(&token_address, &true);
+    assert_eq!(create(Expiry::Ledger(500)), err(EscrowError::TokenBanned));
+    escrow_client.set_token_banned(&token_address, &false);
+    assert_eq!(create(Expiry::Ledger(500)), None);
+}
+
+#[test]
+fn test_initialize_only_once() {
+    let (env, escrow_contract, _token_address, admin, sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    let res = escrow_client.try_initialize(&sender);
+    assert_eq!(
+        res.err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::AlreadyInitialized)
+    );
+    assert_eq!(escrow_client.get_admin(), admin);
+}
+
+#[test]
+fn test_two_step_admin_rotation() {
+    let (env, escrow_contract, token_address, admin, _sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let new_admin = Address::generate(&env);
+
+    let res = escrow_client.try_accept_admin();
+    assert_eq!(
+        res.err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::NoPendingAdmin)
+    );
+
+    escrow_client.propose_admin(&new_admin);
+    assert_eq!(env.auths()[0].0, admin);
+    // Proposal alone does not hand over the role
+    assert_eq!(escrow_client.get_admin(), admin);
+
+    escrow_client.accept_admin();
+    assert_eq!(env.auths()[0].0, new_admin);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(topics, (Symbol::new(&env, "admin_changed"),).into_val(&env));
+    let event: AdminChanged = data.into_val(&env);
+    assert_eq!((event.previous, event.admin), (admin, new_admin.clone()));
+    assert_eq!(escrow_client.get_admin(), new_admin);
+
+    // Admin-only calls now require the new admin
+    escrow_client.set_token_banned(&token_address, &true);
+    assert_eq!(env.auths()[0].0, new_admin);
+
+    // The pending proposal was consumed
+    assert!(escrow_client.try_accept_admin().is_err());
+}
+
+/// Creates a ledger-expiring escrow from `sender` and returns its id
+fn create_test_escrow(
+    env: &Env,
+    escrow_client: &EscrowContractClient,
+    token_address: &Address,
+    sender: &Address,
+    recipient: &Address,
+    seed: u8,
+    amount: i128,
+) -> (BytesN<32>, BytesN<32>) {
+    let supplied_id = BytesN::from_array(env, &[seed; 32]);
+    let claim_secret = BytesN::from_array(env, &[seed.wrapping_add(100); 32]);
+    let escrow_id = scoped_escrow_id(env, sender, &supplied_id);
+    let hash = recipient_hash(env, &escrow_client.address, &escrow_id, recipient, &claim_secret);
+    escrow_client.create_escrow(
+        &supplied_id,
+        sender,
+        &hash,
+        token_address,
+        &amount,
+        &Expiry::Ledger(env.ledger().sequence() + 100),
+    );
+    (escrow_id, claim_secret)
+}
+
+#[test]
+fn test_guardian_threshold_refund() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let guardians = [
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    ];
+    let outsider = Address::generate(&env);
+
+    escrow_client.set_guardians(
+        &GuardianSet {
+            guardians: soroban_sdk::vec![
+                &env,
+                guardians[0].clone(),
+                guardians[1].clone(),
+                guardians[2].clone()
+            ],
+            threshold: 2,
+            proposal_ttl: 3600,
+        },
+        &soroban_sdk::vec![&env],
+    );
+    let (escrow_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 400);
+
+    // A single admin key can no longer force a refund
+    assert_eq!(
+        escrow_client.try_admin_refund(&escrow_id, &1).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::GuardianApprovalRequired)
+    );
+    assert_eq!(
+        escrow_client
+            .try_propose_refund(&outsider, &escrow_id, &1)
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::NotGuardian)
+    );
+
+    let proposal_id = escrow_client.propose_refund(&guardians[0], &escrow_id, &1);
+    assert_eq!(env.auths()[0].0, guardians[0]);
+    assert!(!escrow_client.get_escrow(&escrow_id).is_refunded);
+    assert_eq!(
+        escrow_client
+            .try_approve_refund_proposal(&guardians[0], &proposal_id)
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::AlreadyApproved)
+    );
+
+    escrow_client.approve_refund_proposal(&guardians[2], &proposal_id);
+    assert!(escrow_client.get_refund_proposal(&proposal_id).executed);
+    let queued = escrow_client.get_queued_refund(&escrow_id).unwrap();
+    env.ledger().with_mut(|li| li.timestamp = queued.executable_at);
+    escrow_client.execute_admin_refund(&escrow_id);
+    assert!(escrow_client.get_escrow(&escrow_id).is_refunded);
+    assert_eq!(token_client.balance(&sender), 1000);
+
+    assert_eq!(
+        escrow_client
+            .try_approve_refund_proposal(&guardians[1], &proposal_id)
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::ProposalExecuted)
+    );
+}
+
+#[test]
+fn test_guardian_proposal_expires() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let guardians = [Address::generate(&env), Address::generate(&env)];
+
+    escrow_client.set_guardians(
+        &GuardianSet {
+            guardians: soroban_sdk::vec![&env, guardians[0].clone(), guardians[1].clone()],
+            threshold: 2,
+            proposal_ttl: 60,
+        },
+        &soroban_sdk::vec![&env],
+    );
+    let (escrow_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 400);
+
+    let proposal_id = escrow_client.propose_refund(&guardians[0], &escrow_id, &1);
+    env.ledger().with_mut(|li| li.timestamp += 61);
+    assert_eq!(
+        escrow_client
+            .try_approve_refund_proposal(&guardians[1], &proposal_id)
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::ProposalExpired)
+    );
+    assert!(!escrow_client.get_escrow(&escrow_id).is_refunded);
+}
+
+#[test]
+fn test_admin_cannot_replace_guardians_alone() {
+    let (env, escrow_contract, _token_address, admin, _sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let guardians = [
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    ];
+    let outsider = Address::generate(&env);
+
+    let current = GuardianSet {
+        guardians: soroban_sdk::vec![
+            &env,
+            guardians[0].clone(),
+            guardians[1].clone(),
+            guardians[2].clone()
+        ],
+        threshold: 2,
+        proposal_ttl: 3600,
+    };
+    escrow_client.set_guardians(&current, &soroban_sdk::vec![&env]);
+
+    // A 1-of-1 set of the admin would let them force refunds unchecked
+    let takeover = GuardianSet {
+        guardians: soroban_sdk::vec![&env, admin.clone()],
+        threshold: 1,
+        proposal_ttl: 3600,
+    };
+    let replace = |approvers: soroban_sdk::Vec<Address>| {
+        escrow_client
+            .try_set_guardians(&takeover, &approvers)
+            .err()
+            .map(|e| e.unwrap())
+    };
+    let err = |e: EscrowError| Some(soroban_sdk::Error::from(e));
+    assert_eq!(
+        replace(soroban_sdk::vec![&env]),
+        err(EscrowError::GuardianApprovalRequired)
+    );
+    assert_eq!(
+        replace(soroban_sdk::vec![&env, guardians[0].clone()]),
+        err(EscrowError::GuardianApprovalRequired)
+    );
+    assert_eq!(
+        replace(soroban_sdk::vec![&env, guardians[0].clone(), guardians[0].clone()]),
+        err(EscrowError::AlreadyApproved)
+    );
+    assert_eq!(
+        replace(soroban_sdk::vec![&env, guardians[0].clone(), admin.clone()]),
+        err(EscrowError::NotGuardian)
+    );
+    assert_eq!(
+        replace(soroban_sdk::vec![&env, guardians[0].clone(), outsider]),
+        err(EscrowError::NotGuardian)
+    );
+    assert_eq!(escrow_client.get_guardians().unwrap().threshold, 2);
+
+    escrow_client.set_guardians(
+        &takeover,
+        &soroban_sdk::vec![&env, guardians[0].clone(), guardians[2].clone()],
+    );
+    let signers: std::vec::Vec<Address> = env.auths().into_iter().map(|(a, _)| a).collect();
+    assert!(signers.contains(&admin));
+    assert!(signers.contains(&guardians[0]));
+    assert!(signers.contains(&guardians[2]));
+    assert_eq!(escrow_client.get_guardians().unwrap().threshold, 1);
+}
+
+#[test]
+fn test_invalid_guardian_sets_rejected() {
+    let (env, escrow_contract, _token_address, _admin, _sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let g = Address::generate(&env);
+
+    for (guardians, threshold) in [
+        (soroban_sdk::vec![&env, g.clone()], 0),
+        (soroban_sdk::vec![&env, g.clone()], 2),
+        (soroban_sdk::vec![&env, g.clone(), g.clone()], 2),
+    ] {
+        let res = escrow_client.try_set_guardians(
+            &GuardianSet {
+                guardians,
+                threshold,
+                proposal_ttl: 60,
+            },
+            &soroban_sdk::vec![&env],
+        );
+        assert_eq!(
+            res.err().unwrap().unwrap(),
+            soroban_sdk::Error::from(EscrowError::InvalidGuardianSet)
+        );
+    }
+    assert!(escrow_client.get_guardians().is_none());
+}
+
+#[test]
+fn test_admin_refund_is_timelocked() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    escrow_client.set_refund_delay(&3600);
+
+    let (escrow_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 400);
+    assert_eq!(
+        escrow_client.try_execute_admin_refund(&escrow_id).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::RefundNotQueued)
+    );
+
+    let start = env.ledger().timestamp();
+    escrow_client.admin_refund(&escrow_id, &7);
+    let queued = escrow_client.get_queued_refund(&escrow_id).unwrap();
+    assert_eq!((queued.reason, queued.executable_at), (7, start + 3600));
+    assert_eq!(
+        escrow_client.try_admin_refund(&escrow_id, &7).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::RefundAlreadyQueued)
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = start + 3599);
+    assert_eq!(
+        escrow_client.try_execute_admin_refund(&escrow_id).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::TimelockActive)
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = start + 3600);
+    escrow_client.execute_admin_refund(&escrow_id);
+    assert_eq!(token_client.balance(&sender), 1000);
+
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_admin_refunded"), escrow_id.clone(), sender.clone()).into_val(&env)
+    );
+    let event: EscrowAdminRefunded = data.into_val(&env);
+    assert_eq!((event.escrow_id, event.sender, event.reason), (escrow_id, sender, 7));
+    assert_eq!((event.token, event.amount), (token_address, 400));
+}
+
+#[test]
+fn test_refund_delay_has_a_minimum() {
+    let (env, escrow_contract, _token_address, _admin, _sender, _recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    for delay in [0, MIN_REFUND_DELAY - 1] {
+        assert_eq!(
+            escrow_client.try_set_refund_delay(&delay).err().unwrap().unwrap(),
+            soroban_sdk::Error::from(EscrowError::RefundDelayTooShort)
+        );
+    }
+    assert_eq!(escrow_client.get_refund_delay(), DEFAULT_REFUND_DELAY);
+
+    escrow_client.set_refund_delay(&MIN_REFUND_DELAY);
+    assert_eq!(escrow_client.get_refund_delay(), MIN_REFUND_DELAY);
+}
+
+#[test]
+fn test_recipient_can_claim_during_admin_refund_delay() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let (escrow_id, claim_secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 400);
+    escrow_client.admin_refund(&escrow_id, &2);
+
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(token_client.balance(&recipient), 400);
+    assert!(escrow_client.get_queued_refund(&escrow_id).is_none());
+
+    env.ledger().with_mut(|li| li.timestamp += DEFAULT_REFUND_DELAY);
+    assert!(escrow_client.try_execute_admin_refund(&escrow_id).is_err());
+    assert_eq!(token_client.balance(&sender), 600);
+}
+
+#[test]
+fn test_partial_claims_then_refund_remainder() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let (escrow_id, claim_secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 900);
+
+    escrow_client.claim_partial(&escrow_id, &recipient, &claim_secret, &300);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_tranche_claimed"), escrow_id.clone(), sender.clone()).into_val(&env)
+    );
+    let event: EscrowTrancheClaimed = data.into_val(&env);
+    assert_eq!((event.token, event.amount, event.remaining), (token_address.clone(), 300, 600));
+
+    escrow_client.claim_partial(&escrow_id, &recipient, &claim_secret, &200);
+    let escrow_data = escrow_client.get_escrow(&escrow_id);
+    assert_eq!(escrow_data.claimed_amount, 500);
+    assert!(!escrow_data.is_claimed);
+
+    for bad in [0i128, -1, 401] {
+        assert_eq!(
+            escrow_client
+                .try_claim_partial(&escrow_id, &recipient, &claim_secret, &bad)
+                .err()
+                .unwrap()
+                .unwrap(),
+            soroban_sdk::Error::from(EscrowError::InvalidClaimAmount)
+        );
+    }
+
+    // Only the unclaimed remainder goes back to the sender
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund(&escrow_id);
+    assert_eq!(token_client.balance(&recipient), 500);
+    assert_eq!(token_client.balance(&sender), 100 + 400);
+    assert_eq!(token_client.balance(&escrow_contract), 0);
+}
+
+#[test]
+fn test_claim_takes_remaining_after_tranche() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let (escrow_id, claim_secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 900);
+
+    escrow_client.claim_partial(&escrow_id, &recipient, &claim_secret, &250);
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(token_client.balance(&recipient), 900);
+
+    let escrow_data = escrow_client.get_escrow(&escrow_id);
+    assert!(escrow_data.is_claimed);
+    assert_eq!(escrow_data.claimed_amount, 900);
+    assert!(escrow_client
+        .try_claim_partial(&escrow_id, &recipient, &claim_secret, &1)
+        .is_err());
+}
+
+#[test]
+fn test_milestone_claims_capped_at_released() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let supplied_id = BytesN::from_array(&env, &[7u8; 32]);
+    let claim_secret = BytesN::from_array(&env, &[107u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
+    let hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
+    escrow_client.create_milestone_escrow(
+        &supplied_id,
+        &sender,
+        &hash,
+        &token_address,
+        &900,
+        &Expiry::Ledger(env.ledger().sequence() + 100),
+        &None,
+    );
+    let invalid_claim = Some(soroban_sdk::Error::from(EscrowError::InvalidClaimAmount));
+
+    // Nothing is claimable before the sender releases a tranche
+    assert_eq!(
+        escrow_client
+            .try_claim_partial(&escrow_id, &recipient, &claim_secret, &1)
+            .err()
+            .map(|e| e.unwrap()),
+        invalid_claim
+    );
+    assert_eq!(
+        escrow_client
+            .try_claim(&escrow_id, &recipient, &claim_secret)
+            .err()
+            .map(|e| e.unwrap()),
+        invalid_claim
+    );
+
+    escrow_client.release_tranche(&escrow_id, &300);
+    assert_eq!(env.auths()[0].0, sender);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_tranche_released"), escrow_id.clone(), sender.clone()).into_val(&env)
+    );
+    let event: EscrowTrancheReleased = data.into_val(&env);
+    assert_eq!((event.releaser, event.amount, event.unlocked), (sender.clone(), 300, 300));
+
+    assert_eq!(
+        escrow_client
+            .try_claim_partial(&escrow_id, &recipient, &claim_secret, &301)
+            .err()
+            .map(|e| e.unwrap()),
+        invalid_claim
+    );
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(token_client.balance(&recipient), 300);
+    assert!(!escrow_client.get_escrow(&escrow_id).is_claimed);
+
+    for bad in [0i128, -1, 601] {
+        assert_eq!(
+            escrow_client.try_release_tranche(&escrow_id, &bad).err().unwrap().unwrap(),
+            soroban_sdk::Error::from(EscrowError::InvalidReleaseAmount)
+        );
+    }
+    escrow_client.release_tranche(&escrow_id, &600);
+    escrow_client.claim_partial(&escrow_id, &recipient, &claim_secret, &600);
+    assert_eq!(token_client.balance(&recipient), 900);
+    assert!(escrow_client.get_escrow(&escrow_id).is_claimed);
+}
+
+#[test]
+fn test_arbiter_releases_milestone_tranches() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let arbiter = Address::generate(&env);
+
+    let supplied_id = BytesN::from_array(&env, &[8u8; 32]);
+    let claim_secret = BytesN::from_array(&env, &[108u8; 32]);
+    let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
+    let hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
+    escrow_client.create_milestone_escrow(
+        &supplied_id,
+        &sender,
+        &hash,
+        &token_address,
+        &400,
+        &Expiry::Ledger(env.ledger().sequence() + 100),
+        &Some(arbiter.clone()),
+    );
+
+    escrow_client.release_tranche(&escrow_id, &100);
+    assert_eq!(env.auths()[0].0, arbiter);
+    let escrow = escrow_client.get_escrow(&escrow_id);
+    assert_eq!(escrow.unlocked_amount, 100);
+    assert_eq!(escrow.arbiter_status, ArbiterStatus::Released);
+
+    // The arbiter's release stands: the sender can no longer dispute it
+    assert_eq!(
+        escrow_client.try_dispute(&escrow_id).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::AlreadyReleased)
+    );
+
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(token_client.balance(&recipient), 100);
+    assert_eq!(escrow_client.get_escrow(&escrow_id).claimed_amount, 100);
+}
+
+/// Creates an arbitrated escrow from `sender` and returns its id
+fn create_arbitrated_escrow(
+    env: &Env,
+    escrow_client: &EscrowContractClient,
+    token_address: &Address,
+    sender: &Address,
+    recipient: &Address,
+    arbiter: &Address,
+) -> (BytesN<32>, BytesN<32>) {
+    let supplied_id = BytesN::from_array(env, &[9u8; 32]);
+    let claim_secret = BytesN::from_array(env, &[109u8; 32]);
+    let escrow_id = scoped_escrow_id(env, sender, &supplied_id);
+    let hash = recipient_hash(env, &escrow_client.address, &escrow_id, recipient, &claim_secret);
+    escrow_client.create_escrow_with_arbiter(
+        &supplied_id,
+        sender,
+        &hash,
+        token_address,
+        &400,
+        &Expiry::Ledger(env.ledger().sequence() + 100),
+        &Some(arbiter.clone()),
+    );
+    (escrow_id, claim_secret)
+}
+
+#[test]
+fn test_arbiter_release_gates_claim() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let arbiter = Address::generate(&env);
+
+    let (escrow_id, claim_secret) =
+        create_arbitrated_escrow(&env, &escrow_client, &token_address, &sender, &recipient, &arbiter);
+
+    assert_eq!(
+        escrow_client
+            .try_claim(&escrow_id, &recipient, &claim_secret)
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::ReleaseNotApproved)
+    );
+
+    escrow_client.approve_release(&escrow_id);
+    assert_eq!(env.auths()[0].0, arbiter);
+    assert_eq!(
+        escrow_client.get_escrow(&escrow_id).arbiter_status,
+        ArbiterStatus::Released
+    );
+
+    // A released escrow can no longer be disputed
+    assert_eq!(
+        escrow_client.try_dispute(&escrow_id).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::AlreadyReleased)
+    );
+
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(token_client.balance(&recipient), 400);
+}
+
+#[test]
+fn test_arbiter_refund_after_dispute() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let arbiter = Address::generate(&env);
+
+    let (escrow_id, claim_secret) =
+        create_arbitrated_escrow(&env, &escrow_client, &token_address, &sender, &recipient, &arbiter);
+
+    escrow_client.dispute(&escrow_id);
+    assert_eq!(env.auths()[0].0, sender);
+    assert_eq!(
+        escrow_client.get_escrow(&escrow_id).arbiter_status,
+        ArbiterStatus::Disputed
+    );
+
+    // Arbiter refunds before expiry
+    escrow_client.approve_refund(&escrow_id);
+    assert_eq!(env.auths()[0].0, arbiter);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_arbiter_refunded"), escrow_id.clone(), sender.clone()).into_val(&env)
+    );
+    let event: EscrowArbiterRefunded = data.into_val(&env);
+    assert_eq!((event.arbiter, event.amount), (arbiter, 400));
+    assert_eq!(token_client.balance(&sender), 1000);
+    assert!(escrow_client.try_claim(&escrow_id, &recipient, &claim_secret).is_err());
+    assert!(escrow_client.try_approve_release(&escrow_id).is_err());
+}
+
+#[test]
+fn test_silent_arbiter_falls_back_to_expiry_refund() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let arbiter = Address::generate(&env);
+
+    let (escrow_id, _) =
+        create_arbitrated_escrow(&env, &escrow_client, &token_address, &sender, &recipient, &arbiter);
+    escrow_client.dispute(&escrow_id);
+
+    assert!(escrow_client.try_refund(&escrow_id).is_err());
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund(&escrow_id);
+    assert_eq!(token_client.balance(&sender), 1000);
+}
+
+#[test]
+fn test_arbiter_calls_need_an_arbiter() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    let (escrow_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 100);
+    for res in [
+        escrow_client.try_approve_release(&escrow_id).err(),
+        escrow_client.try_approve_refund(&escrow_id).err(),
+        escrow_client.try_dispute(&escrow_id).err(),
+    ] {
+        assert_eq!(
+            res.unwrap().unwrap(),
+            soroban_sdk::Error::from(EscrowError::NoArbiter)
+        );
+    }
+}
+
+#[test]
+fn test_htlc_claim_reveals_preimage() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let secret = BytesN::from_array(&env, &[42u8; 32]);
+    let hashlock: BytesN<32> = env.crypto().sha256(&secret.clone().into()).into();
+    let escrow_id = escrow_client.create_htlc(
+        &BytesN::from_array(&env, &[1u8; 32]),
+        &sender,
+        &recipient,
+        &hashlock,
+        &HashAlgorithm::Sha256,
+        &token_address,
+        &250,
+        &Expiry::Timestamp(env.ledger().timestamp() + 3_600),
+    );
+
+    // The hashlock alone is not enough; the stored recipient must claim
+    let stranger = Address::generate(&env);
+    assert!(escrow_client.try_claim(&escrow_id, &stranger, &secret).is_err());
+    let wrong = BytesN::from_array(&env, &[43u8; 32]);
+    assert!(escrow_client.try_claim(&escrow_id, &recipient, &wrong).is_err());
+
+    escrow_client.claim(&escrow_id, &recipient, &secret);
+    assert_eq!(token_client.balance(&recipient), 250);
+
+    let topics = (Symbol::new(&env, "htlc_claimed"), hashlock).into_val(&env);
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .find(|(_, t, _)| t.clone() == topics)
+        .unwrap();
+    let (id, claimer, preimage): (BytesN<32>, Address, BytesN<32>) = data.into_val(&env);
+    assert_eq!((id, claimer, preimage), (escrow_id, recipient, secret));
+}
+
+#[test]
+fn test_htlc_timelock_refund() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let secret = BytesN::from_array(&env, &[42u8; 32]);
+    let hashlock: BytesN<32> = env.crypto().sha256(&secret.clone().into()).into();
+    let expires_at = env.ledger().timestamp() + 3_600;
+    let escrow_id = escrow_client.create_htlc(
+        &BytesN::from_array(&env, &[1u8; 32]),
+        &sender,
+        &recipient,
+        &hashlock,
+        &HashAlgorithm::Sha256,
+        &token_address,
+        &250,
+        &Expiry::Timestamp(expires_at),
+    );
+
+    assert!(escrow_client.try_refund(&escrow_id).is_err());
+    env.ledger().with_mut(|li| li.timestamp = expires_at);
+    escrow_client.refund(&escrow_id);
+    assert_eq!(token_client.balance(&sender), 1000);
+    assert!(escrow_client.try_claim(&escrow_id, &recipient, &secret).is_err());
+}
+
+#[test]
+fn test_htlc_hashlock_known_vectors() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    // Hashes of 32 zero bytes
+    let sha256_zero: [u8; 32] = [
+        0x66, 0x68, 0x7a, 0xad, 0xf8, 0x62, 0xbd, 0x77, 0x6c, 0x8f, 0xc1, 0x8b, 0x8e, 0x9f, 0x8e,
+        0x20, 0x08, 0x97, 0x14, 0x85, 0x6e, 0xe2, 0x33, 0xb3, 0x90, 0x2a, 0x59, 0x1d, 0x0d, 0x5f,
+        0x29, 0x25,
+    ];
+    let keccak256_zero: [u8; 32] = [
+        0x29, 0x0d, 0xec, 0xd9, 0x54, 0x8b, 0x62, 0xa8, 0xd6, 0x03, 0x45, 0xa9, 0x88, 0x38, 0x6f,
+        0xc8, 0x4b, 0xa6, 0xbc, 0x95, 0x48, 0x40, 0x08, 0xf6, 0x36, 0x2f, 0x93, 0x16, 0x0e, 0xf3,
+        0xe5, 0x63,
+    ];
+    let secret = BytesN::from_array(&env, &[0u8; 32]);
+    let expiry = Expiry::Ledger(env.ledger().sequence() + 100);
+
+    let mut ids = soroban_sdk::Vec::new(&env);
+    for (seed, lock, algorithm) in [
+        (1u8, sha256_zero, HashAlgorithm::Sha256),
+        (2u8, keccak256_zero, HashAlgorithm::Keccak256),
+        // A lock paired with the wrong algorithm never matches
+        (3u8, sha256_zero, HashAlgorithm::Keccak256),
+    ] {
+        ids.push_back(escrow_client.create_htlc(
+            &BytesN::from_array(&env, &[seed; 32]),
+            &sender,
+            &recipient,
+            &BytesN::from_array(&env, &lock),
+            &algorithm,
+            &token_address,
+            &100,
+            &expiry,
+        ));
+    }
+
+    escrow_client.claim(&ids.get_unchecked(0), &recipient, &secret);
+    escrow_client.claim(&ids.get_unchecked(1), &recipient, &secret);
+    assert!(escrow_client
+        .try_claim(&ids.get_unchecked(2), &recipient, &secret)
+        .is_err());
+    assert_eq!(token_client.balance(&recipient), 200);
+    assert_eq!(
+        escrow_client.get_escrow(&ids.get_unchecked(1)).hash_algorithm,
+        HashAlgorithm::Keccak256
+    );
+}
+
+#[test]
+fn test_signed_claim_is_recipient_bound_and_single_use() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+    let claim_key = BytesN::from_array(&env, &signing_key.verifying_key().to_bytes());
+    let escrow_id = escrow_client.create_escrow_signed(
+        &BytesN::from_array(&env, &[1u8; 32]),
+        &sender,
+        &claim_key,
+        &token_address,
+        &300,
+        &Expiry::Ledger(env.ledger().sequence() + 100),
+    );
+
+    let sign = |who: &Address| {
+        let mut message = [0u8; 256];
+        let bytes = claim_message(&env, &escrow_contract, &escrow_id, who);
+        let len = bytes.len() as usize;
+        bytes.copy_into_slice(&mut message[..len]);
+        BytesN::from_array(&env, &signing_key.sign(&message[..len]).to_bytes())
+    };
+    let signature = sign(&recipient);
+
+    // The signature only authorises the recipient it was issued for
+    let stranger = Address::generate(&env);
+    assert!(escrow_client
+        .try_claim_signed(&escrow_id, &stranger, &signature)
+        .is_err());
+    // Secret-based claims do not apply
+    let secret = BytesN::from_array(&env, &[0u8; 32]);
+    assert!(escrow_client.try_claim(&escrow_id, &recipient, &secret).is_err());
+
+    escrow_client.claim_signed(&escrow_id, &recipient, &signature);
+    assert_eq!(env.auths()[0].0, recipient);
+    assert_eq!(token_client.balance(&recipient), 300);
+
+    assert!(escrow_client
+        .try_claim_signed(&escrow_id, &recipient, &signature)
+        .is_err());
+}
+
+#[test]
+fn test_signed_claim_rejects_other_keys() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    let claim_key = SigningKey::from_bytes(&[7u8; 32]).verifying_key().to_bytes();
+    let escrow_id = escrow_client.create_escrow_signed(
+        &BytesN::from_array(&env, &[1u8; 32]),
+        &sender,
+        &BytesN::from_array(&env, &claim_key),
+        &token_address,
+        &300,
+        &Expiry::Ledger(env.ledger().sequence() + 100),
+    );
+
+    let mut message = [0u8; 256];
+    let bytes = claim_message(&env, &escrow_contract, &escrow_id, &recipient);
+    let len = bytes.len() as usize;
+    bytes.copy_into_slice(&mut message[..len]);
+    let forged = SigningKey::from_bytes(&[8u8; 32]).sign(&message[..len]);
+    assert!(escrow_client
+        .try_claim_signed(&escrow_id, &recipient, &BytesN::from_array(&env, &forged.to_bytes()))
+        .is_err());
+
+    // Secret escrows have no claim key
+    let (plain_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 2, 100);
+    assert_eq!(escrow_client.get_escrow(&plain_id).claim_key, None);
+    assert_eq!(
+        escrow_client.get_escrow(&escrow_id).claim_key,
+        Some(Bytes::from_array(&env, &claim_key))
+    );
+    let signature = SigningKey::from_bytes(&[7u8; 32]).sign(&message[..len]);
+    assert!(escrow_client
+        .try_claim_signed(&plain_id, &recipient, &BytesN::from_array(&env, &signature.to_bytes()))
+        .is_err());
+}
+
+#[test]
+fn test_list_escrows_by_sender_with_status_filter() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    let mut ids = soroban_sdk::Vec::new(&env);
+    let mut secrets = soroban_sdk::Vec::new(&env);
+    for seed in 1..=4u8 {
+        let (id, secret) =
+            create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, seed, 100);
+        ids.push_back(id);
+        secrets.push_back(secret);
+    }
+    // Another sender's escrows stay out of this index
+    let other = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&other, &100);
+    create_test_escrow(&env, &escrow_client, &token_address, &other, &recipient, 9, 100);
+
+    escrow_client.claim(&ids.get_unchecked(0), &recipient, &secrets.get_unchecked(0));
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund(&ids.get_unchecked(1));
+    // A fresh escrow that has not expired yet
+    let (fresh, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 5, 100);
+
+    assert_eq!(escrow_client.count_escrows_by_sender(&sender), 5);
+    let all = escrow_client.list_escrows_by_sender(&sender, &0, &10, &None);
+    let expected = [
+        EscrowStatus::Claimed,
+        EscrowStatus::Refunded,
+        EscrowStatus::Expired,
+        EscrowStatus::Expired,
+        EscrowStatus::Active,
+    ];
+    assert_eq!(all.len(), 5);
+    for (entry, status) in all.iter().zip(expected) {
+        assert_eq!(entry.status, status);
+    }
+    assert_eq!(all.get_unchecked(0).escrow_id, ids.get_unchecked(0));
+
+    let expired =
+        escrow_client.list_escrows_by_sender(&sender, &0, &10, &Some(EscrowStatus::Expired));
+    assert_eq!(expired.len(), 2);
+    assert_eq!(expired.get_unchecked(1).escrow_id, ids.get_unchecked(3));
+    let active =
+        escrow_client.list_escrows_by_sender(&sender, &0, &10, &Some(EscrowStatus::Active));
+    assert_eq!(active.get_unchecked(0).escrow_id, fresh);
+
+    // Pagination walks index slots
+    let page = escrow_client.list_escrows_by_sender(&sender, &3, &10, &None);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get_unchecked(0).escrow_id, ids.get_unchecked(3));
+    assert_eq!(escrow_client.list_escrows_by_sender(&sender, &5, &10, &None).len(), 0);
+}
+
+#[test]
+fn test_expired_refund_is_permissionless() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let (escrow_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 500);
+    assert!(escrow_client.try_refund(&escrow_id).is_err());
+
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund(&escrow_id);
+    // No signature was needed, and the funds went to the sender
+    assert!(env.auths().is_empty());
+    assert_eq!(token_client.balance(&sender), 1000);
+}
+
+#[test]
+fn test_keeper_refund_pays_incentive() {
+    let (env, escrow_contract, token_address, admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let keeper = Address::generate(&env);
+
+    assert_eq!(
+        escrow_client
+            .try_set_keeper_fee(&(MAX_KEEPER_FEE_BPS + 1))
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::KeeperFeeTooHigh)
+    );
+    escrow_client.set_keeper_fee(&100);
+    assert_eq!(env.auths()[0].0, admin);
+
+    let (escrow_id, claim_secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 500);
+    assert!(escrow_client.try_refund_expired(&escrow_id, &keeper).is_err());
+
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund_expired(&escrow_id, &keeper);
+    assert_eq!(env.auths()[0].0, keeper);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(topics, (Symbol::new(&env, "keeper_rewarded"),).into_val(&env));
+    let (_, rewarded, fee): (BytesN<32>, Address, i128) = data.into_val(&env);
+    assert_eq!((rewarded, fee), (keeper.clone(), 5));
+
+    assert_eq!(token_client.balance(&keeper), 5);
+    assert_eq!(token_client.balance(&sender), 500 + 495);
+    assert!(escrow_client.try_claim(&escrow_id, &recipient, &claim_secret).is_err());
+    assert_solvent(&escrow_client, &token_address);
+}
+
+#[test]
+fn test_batch_create_uses_one_transfer() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let expiry = Expiry::Ledger(env.ledger().sequence() + 100);
+    let mut specs = soroban_sdk::Vec::new(&env);
+    let mut secrets = soroban_sdk::Vec::new(&env);
+    for seed in 1..=3u8 {
+        let supplied_id = BytesN::from_array(&env, &[seed; 32]);
+        let secret = BytesN::from_array(&env, &[seed + 100; 32]);
+        let escrow_id = scoped_escrow_id(&env, &sender, &supplied_id);
+        specs.push_back(EscrowSpec {
+            escrow_id: supplied_id,
+            recipient_hash: recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &secret),
+            amount: 100 * seed as i128,
+            expiry,
+        });
+        secrets.push_back(secret);
+    }
+
+    let ids = escrow_client.create_escrows_batch(&sender, &token_address, &specs);
+    assert_eq!(ids.len(), 3);
+    assert_eq!(token_client.balance(&sender), 1000 - 600);
+    let transfers = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, topics, _)| {
+            *contract == token_address
+                && Symbol::from_val(&env, &topics.get_unchecked(0)) == Symbol::new(&env, "transfer")
+        })
+        .count();
+    assert_eq!(transfers, 1);
+
+    escrow_client.claim(&ids.get_unchecked(1), &recipient, &secrets.get_unchecked(1));
+    assert_eq!(token_client.balance(&recipient), 200);
+    assert_eq!(escrow_client.count_escrows_by_sender(&sender), 3);
+    assert_solvent(&escrow_client, &token_address);
+
+    // One invalid spec aborts the whole batch
+    let mut bad = soroban_sdk::Vec::new(&env);
+    bad.push_back(EscrowSpec { amount: 0, ..specs.get_unchecked(0) });
+    assert!(escrow_client
+        .try_create_escrows_batch(&sender, &token_address, &bad)
+        .is_err());
+    let mut too_many = soroban_sdk::Vec::new(&env);
+    for _ in 0..=MAX_BATCH {
+        too_many.push_back(specs.get_unchecked(0));
+    }
+    assert_eq!(
+        escrow_client
+            .try_create_escrows_batch(&sender, &token_address, &too_many)
+            .err()
+            .unwrap()
+            .unwrap(),
+        soroban_sdk::Error::from(EscrowError::BatchTooLarge)
+    );
+}
+
+#[test]
+fn test_refund_expired_batch_reports_outcomes() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let (expired, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 100);
+    let (claimed, secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 2, 100);
+    escrow_client.claim(&claimed, &recipient, &secret);
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    let (fresh, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 3, 100);
+    let unknown = BytesN::from_array(&env, &[0u8; 32]);
+
+    let outcomes = escrow_client.refund_expired_batch(&soroban_sdk::vec![
+        &env,
+        expired.clone(),
+        claimed,
+        fresh,
+        unknown,
+        expired,
+    ]);
+    assert_eq!(
+        outcomes,
+        soroban_sdk::vec![
+            &env,
+            RefundOutcome::Refunded,
+            RefundOutcome::Settled,
+            RefundOutcome::NotExpired,
+            RefundOutcome::NotFound,
+            RefundOutcome::Settled,
+        ]
+    );
+    assert_eq!(token_client.balance(&sender), 1000 - 100 - 100);
+    assert_solvent(&escrow_client, &token_address);
+}
+
+#[test]
+fn test_refund_expired_batch_survives_failed_transfers() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+    let token_client = token::Client::new(&env, &token_address);
+    let frozen_asset = env.register_stellar_asset_contract_v2(Address::generate(&env));
+    frozen_asset.issuer().set_flag(IssuerFlags::RevocableFlag);
+    let frozen_token = frozen_asset.address();
+    let frozen_admin = token::StellarAssetClient::new(&env, &frozen_token);
+    frozen_admin.mint(&sender, &1000);
+
+    let (first, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 100);
+    let (stuck, _) =
+        create_test_escrow(&env, &escrow_client, &frozen_token, &sender, &recipient, 2, 200);
+    let (last, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 3, 300);
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+
+    // The sender can no longer receive the frozen token, so its refund fails
+    frozen_admin.set_authorized(&sender, &false);
+    let ids = soroban_sdk::vec![&env, first, stuck.clone(), last];
+    assert_eq!(
+        escrow_client.refund_expired_batch(&ids),
+        soroban_sdk::vec![
+            &env,
+            RefundOutcome::Refunded,
+            RefundOutcome::Failed,
+            RefundOutcome::Refunded,
+        ]
+    );
+    assert_eq!(token_client.balance(&sender), 1000);
+    assert!(!escrow_client.get_escrow(&stuck).is_refunded);
+    assert_solvent(&escrow_client, &token_address);
+    assert_solvent(&escrow_client, &frozen_token);
+    let reports = escrow_client.solvency(&soroban_sdk::vec![&env, frozen_token.clone()]);
+    assert_eq!(reports.get(0).unwrap().liabilities, 200);
+
+    // It stays refundable once the transfer can go through
+    frozen_admin.set_authorized(&sender, &true);
+    assert_eq!(
+        escrow_client.refund_expired_batch(&ids),
+        soroban_sdk::vec![
+            &env,
+            RefundOutcome::Settled,
+            RefundOutcome::Refunded,
+            RefundOutcome::Settled,
+        ]
+    );
+    assert_eq!(token::Client::new(&env, &frozen_token).balance(&sender), 1000);
+    assert_solvent(&escrow_client, &frozen_token);
+}
+
+#[test]
+fn test_archive_tombstones_supplied_ids() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    let (escrow_id, claim_secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 100);
+    assert_eq!(
+        escrow_client.try_archive(&escrow_id).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::NotSettled)
+    );
+
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    assert_eq!(
+        escrow_client.try_archive(&escrow_id).err().unwrap().unwrap(),
+        soroban_sdk::Error::from(EscrowError::GracePeriodActive)
+    );
+
+    env.ledger().with_mut(|li| li.timestamp += DEFAULT_ARCHIVE_GRACE);
+    escrow_client.archive(&escrow_id);
+    assert!(escrow_client.try_get_escrow(&escrow_id).is_err());
+    let tombstone = escrow_client.get_tombstone(&escrow_id).unwrap();
+    assert_eq!((tombstone.sender, tombstone.status), (sender.clone(), EscrowStatus::Claimed));
+    assert_eq!(escrow_client.list_escrows_by_sender(&sender, &0, &10, &None).len(), 0);
+
+    // Neither the claim nor the id can be replayed
+    assert!(escrow_client.try_claim(&escrow_id, &recipient, &claim_secret).is_err());
+    assert!(escrow_client.try_archive(&escrow_id).is_err());
+    let hash = recipient_hash(&env, &escrow_contract, &escrow_id, &recipient, &claim_secret);
+    assert!(escrow_client
+        .try_create_escrow(
+            &BytesN::from_array(&env, &[1u8; 32]),
+            &sender,
+            &hash,
+            &token_address,
+            &100,
+            &Expiry::Ledger(env.ledger().sequence() + 100),
+        )
+        .is_err());
+    assert_eq!(
+        escrow_client.refund_expired_batch(&soroban_sdk::vec![&env, escrow_id]),
+        soroban_sdk::vec![&env, RefundOutcome::Settled]
+    );
+}
+
+#[test]
+fn test_archive_removes_nonce_escrows() {
+    let (env, escrow_contract, token_address, admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    escrow_client.set_archive_grace(&0);
+    assert_eq!(env.auths()[0].0, admin);
+
+    let claim_secret = BytesN::from_array(&env, &[7u8; 32]);
+    let hash = recipient_hash_for_nonce(&env, &escrow_contract, &sender, 0, &recipient, &claim_secret);
+    let expiry = Expiry::Ledger(env.ledger().sequence() + 100);
+    let escrow_id =
+        escrow_client.create_escrow_auto(&sender, &0, &hash, &token_address, &300, &expiry);
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund(&escrow_id);
+
+    escrow_client.archive(&escrow_id);
+    assert!(escrow_client.try_get_escrow(&escrow_id).is_err());
+    assert!(escrow_client.get_tombstone(&escrow_id).is_none());
+
+    // The id is derived from a spent nonce, so it cannot come back
+    let expiry = Expiry::Ledger(env.ledger().sequence() + 100);
+    assert!(escrow_client
+        .try_create_escrow_auto(&sender, &0, &hash, &token_address, &300, &expiry)
+        .is_err());
+    assert!(escrow_client.try_refund(&escrow_id).is_err());
+
+    // Without a tombstone the batch refund cannot tell it was ever settled
+    assert_eq!(
+        escrow_client.refund_expired_batch(&soroban_sdk::vec![&env, escrow_id]),
+        soroban_sdk::vec![&env, RefundOutcome::NotFound]
+    );
+}
+
+#[test]
+fn test_events_carry_indexing_topics_and_full_data() {
+    let (env, escrow_contract, token_address, _admin, sender, recipient) = setup();
+    let escrow_client = EscrowContractClient::new(&env, &escrow_contract);
+
+    let (escrow_id, claim_secret) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 1, 250);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_created"), escrow_id.clone(), sender.clone()).into_val(&env)
+    );
+    let created: EscrowCreated = data.into_val(&env);
+    let stored = escrow_client.get_escrow(&escrow_id);
+    assert_eq!(created.escrow_id, escrow_id);
+    assert_eq!(created.sender, sender);
+    assert_eq!(created.recipient_hash, stored.recipient_hash);
+    assert_eq!((created.token, created.amount), (token_address.clone(), 250));
+    assert_eq!((created.expiry, created.hash_version), (stored.expiry, stored.hash_version));
+
+    escrow_client.claim(&escrow_id, &recipient, &claim_secret);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_claimed"), escrow_id.clone(), sender.clone()).into_val(&env)
+    );
+    let claimed: EscrowClaimed = data.into_val(&env);
+    assert_eq!(claimed.recipient, recipient);
+    assert_eq!((claimed.token, claimed.amount), (token_address.clone(), 250));
+
+    let (refund_id, _) =
+        create_test_escrow(&env, &escrow_client, &token_address, &sender, &recipient, 2, 150);
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    escrow_client.refund(&refund_id);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        topics,
+        (Symbol::new(&env, "escrow_refunded"), refund_id.clone(), sender.clone()).into_val(&env)
+    );
+    let refunded: EscrowRefunded = data.into_val(&env);
+    assert_eq!((refunded.sender, refunded.token, refunded.amount), (sender, token_address, 150));
+}
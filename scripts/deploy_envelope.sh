@@ -20,6 +20,6 @@ $SOROBAN contract invoke \
   --source $CREATOR \
   --network $NETWORK \
   -- \
-  init --reflector_fx "$REFLECTOR_FX_CONTRACT"
+  init --admin "$CREATOR" --reflector_fx "$REFLECTOR_FX_CONTRACT"
 
 echo "Initialized with Reflector FX: $REFLECTOR_FX_CONTRACT"
\ No newline at end of file
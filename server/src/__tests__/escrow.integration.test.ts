@@ -113,7 +113,6 @@ describe('Escrow Integration Tests', () => {
           type: 'ESCROW_FUND',
           payload: {
             senderWalletId: testWallet.id,
-            recipientHash: testEnvelope.hash,
             amountAtomic: '1000000000', // 100 USDC with 7 decimals
             assetCode: 'USDC',
             expiryTs: testEnvelope.expiryTs,
@@ -168,7 +167,6 @@ describe('Escrow Integration Tests', () => {
           type: 'ESCROW_FUND',
           payload: {
             senderWalletId: testWallet.id,
-            recipientHash: failingEnvelope.hash,
             amountAtomic: '1000000000',
             assetCode: 'INVALID_ASSET', // This should cause failure
             expiryTs: failingEnvelope.expiryTs,
@@ -10,9 +10,12 @@ import {
   nativeToScVal,
   Networks,
   BASE_FEE,
-  Keypair
+  Keypair,
+  scValToNative,
+  xdr
 } from '@stellar/stellar-sdk';
 import { Server as SorobanServer } from '@stellar/stellar-sdk/rpc';
+import { scopedEscrowId, recipientHash } from '../lib/stellar';
 import crypto from 'crypto';
 
 const WORKER_ID = `${hostname()}-${process.pid}`;
@@ -225,6 +228,10 @@ export class OutboxWorker {
         await this.handleEscrowFund(payload);
         break;
       
+      case 'ESCROW_EXECUTE_REFUND':
+        await this.handleEscrowExecuteRefund(payload);
+        break;
+      
       case 'NFT_MINT':
         // Stub for future implementation
         console.log('[Outbox] NFT_MINT handler not implemented yet', payload);
@@ -316,13 +323,12 @@ export class OutboxWorker {
    */
   private async handleEscrowFund(payload: {
     senderWalletId: string;
-    recipientHash: string;
     amountAtomic: string;
     assetCode: string;
     expiryTs: number;
     envelopeId: string;
   }): Promise<void> {
-    const { senderWalletId, recipientHash, amountAtomic, assetCode, expiryTs, envelopeId } = payload;
+    const { senderWalletId, amountAtomic, assetCode, expiryTs, envelopeId } = payload;
     
     console.log(`[Outbox] Creating escrow for envelope ${envelopeId}`);
     
@@ -345,12 +351,47 @@ export class OutboxWorker {
         throw new Error(`Wallet not found: ${senderWalletId}`);
       }
 
+      // The recipient and claim secret come from the envelope itself
+      const envelope = await prisma.envelope.findUnique({
+        where: { id: envelopeId }
+      });
+      
+      if (!envelope) {
+        throw new Error(`Envelope not found: ${envelopeId}`);
+      }
+
       // Parse the funding keypair
       const fundingKeypair = Keypair.fromSecret(FUNDING_SECRET_KEY);
       const fundingAccount = await getSorobanServer().getAccount(fundingKeypair.publicKey());
       
-      // Generate a unique escrow ID from the envelope ID
+      // Generate a unique escrow ID from the envelope ID; the contract stores the
+      // escrow under this id scoped to the sender (see scopedEscrowId)
       const escrowId = crypto.createHash('sha256').update(envelopeId).digest();
+      const claimSecret = Buffer.from(envelope.preimage, 'hex');
+
+      // A wallet recipient is bound into recipient_hash. Email and pool envelopes
+      // don't know theirs yet, so they are funded as signed-claim escrows whose
+      // claim key is derived from the preimage
+      const create = envelope.recipient
+        ? {
+            function: 'create_escrow',
+            claim: nativeToScVal(
+              recipientHash(
+                ESCROW_CONTRACT_ID,
+                scopedEscrowId(wallet.publicKey, envelopeId),
+                envelope.recipient,
+                claimSecret
+              ),
+              { type: 'bytes' }
+            ), // recipient_hash
+          }
+        : {
+            function: 'create_escrow_signed',
+            claim: nativeToScVal(
+              Keypair.fromRawEd25519Seed(claimSecret).rawPublicKey(),
+              { type: 'bytes' }
+            ), // claim_key
+          };
       
       // Determine the asset contract address based on assetCode
       // For MVP, we'll use a mapping. In production, this should come from configuration
@@ -372,14 +413,17 @@ export class OutboxWorker {
         .addOperation(
           Operation.invokeContractFunction({
             contract: ESCROW_CONTRACT_ID,
-            function: 'create_escrow',
+            function: create.function,
             args: [
               nativeToScVal(escrowId, { type: 'bytes' }), // escrow_id
               Address.fromString(wallet.publicKey).toScVal(), // sender
-              nativeToScVal(Buffer.from(recipientHash, 'hex'), { type: 'bytes' }), // recipient_hash
+              create.claim,
               Address.fromString(assetContract).toScVal(), // token
               nativeToScVal(BigInt(amountAtomic), { type: 'i128' }), // amount
-              nativeToScVal(Math.floor(expiryTs / 1000), { type: 'u32' }), // expiry_ledger (approximate)
+              xdr.ScVal.scvVec([
+                xdr.ScVal.scvSymbol('Timestamp'),
+                nativeToScVal(BigInt(expiryTs), { type: 'u64' }),
+              ]), // expiry: Expiry::Timestamp(expiryTs), unix seconds
             ],
           })
         )
@@ -404,6 +448,14 @@ export class OutboxWorker {
       }
       
       if (getTransactionResponse.status === 'SUCCESS') {
+        // create_escrow returns the scoped id; refunds and claims must use it
+        const storedId = getTransactionResponse.returnValue
+          ? Buffer.from(scValToNative(getTransactionResponse.returnValue))
+          : null;
+        if (!storedId || !storedId.equals(scopedEscrowId(wallet.publicKey, envelopeId))) {
+          console.error(`[Outbox] Escrow for envelope ${envelopeId} stored under unexpected id: ${storedId?.toString('hex')}`);
+        }
+
         // Update envelope status to FUNDED
         await prisma.envelope.update({
           where: { id: envelopeId },
@@ -437,6 +489,79 @@ export class OutboxWorker {
     }
   }
 
+  /**
+   * Handle escrow execute refund job - executes a forced refund queued by
+   * admin_refund once the contract's refund delay has passed
+   */
+  private async handleEscrowExecuteRefund(payload: {
+    envelopeId: string;
+    escrowId: string;
+    reason: number;
+  }): Promise<void> {
+    const { envelopeId, escrowId, reason } = payload;
+    
+    console.log(`[Outbox] Executing queued refund for envelope ${envelopeId}`);
+    
+    if (!ESCROW_CONTRACT_ID) {
+      throw new Error('ESCROW_CONTRACT_ID not configured');
+    }
+    
+    if (!FUNDING_SECRET_KEY) {
+      throw new Error('Funding account not configured (FUNDING_SECRET_KEY or SOROBAN_ACCOUNT)');
+    }
+    
+    const fundingKeypair = Keypair.fromSecret(FUNDING_SECRET_KEY);
+    const fundingAccount = await getSorobanServer().getAccount(fundingKeypair.publicKey());
+    
+    const tx = new TransactionBuilder(fundingAccount, {
+      fee: BASE_FEE,
+      networkPassphrase: NETWORK_PASSPHRASE,
+    })
+      .addOperation(
+        Operation.invokeContractFunction({
+          contract: ESCROW_CONTRACT_ID,
+          function: 'execute_admin_refund',
+          args: [
+            nativeToScVal(Buffer.from(escrowId, 'hex'), { type: 'bytes' }), // escrow_id
+          ],
+        })
+      )
+      .setTimeout(180)
+      .build();
+    
+    // Fails simulation if the recipient claimed during the delay; retries then
+    // give up and the envelope keeps its claimed state
+    const preparedTx = await getSorobanServer().prepareTransaction(tx);
+    preparedTx.sign(fundingKeypair);
+    
+    const submitResult = await getSorobanServer().sendTransaction(preparedTx);
+    
+    let getTransactionResponse = await getSorobanServer().getTransaction(submitResult.hash);
+    let retries = 0;
+    const maxRetries = 20;
+    
+    while (getTransactionResponse.status === 'NOT_FOUND' && retries < maxRetries) {
+      await new Promise(resolve => setTimeout(resolve, 2000));
+      getTransactionResponse = await getSorobanServer().getTransaction(submitResult.hash);
+      retries++;
+    }
+    
+    if (getTransactionResponse.status !== 'SUCCESS') {
+      throw new Error(`Transaction failed with status: ${getTransactionResponse.status}`);
+    }
+    
+    await prisma.envelope.update({
+      where: { id: envelopeId },
+      data: {
+        status: 'CANCELED',
+        canceledAt: new Date(),
+        cancelReason: `Admin refund executed (reason ${reason})`,
+      }
+    });
+    
+    console.log(`[Outbox] Queued refund executed for envelope ${envelopeId}, tx: ${submitResult.hash}`);
+  }
+
   /**
    * Handle job processing error
    */
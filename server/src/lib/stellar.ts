@@ -69,6 +69,47 @@ export function sha256Hex(preimage: string): string {
   return crypto.createHash('sha256').update(preimage).digest('hex');
 }
 
+const SCOPED_ESCROW_ID_TAG = 'novagift:escrow:id:v1';
+
+/**
+ * Escrow id the escrow contract stores an envelope under. Mirrors
+ * `scoped_escrow_id` in the contract: sha256 over the tag, the sender's
+ * ScVal XDR and the supplied id (sha256 of the envelope id)
+ */
+export function scopedEscrowId(sender: string, envelopeId: string): Buffer {
+  const suppliedId = crypto.createHash('sha256').update(envelopeId).digest();
+  return crypto
+    .createHash('sha256')
+    .update(Buffer.from(SCOPED_ESCROW_ID_TAG))
+    .update(Address.fromString(sender).toScVal().toXDR())
+    .update(suppliedId)
+    .digest();
+}
+
+const RECIPIENT_HASH_TAG = 'novagift:escrow:recipient:v1';
+
+/**
+ * recipient_hash a secret-claimed escrow is checked against. Mirrors
+ * `recipient_hash` in the contract: sha256 over the tag, the escrow contract's
+ * ScVal XDR, the scoped escrow id (see scopedEscrowId), the recipient's ScVal XDR
+ * and the 32-byte claim secret
+ */
+export function recipientHash(
+  contractId: string,
+  escrowId: Buffer,
+  recipient: string,
+  claimSecret: Buffer
+): Buffer {
+  return crypto
+    .createHash('sha256')
+    .update(Buffer.from(RECIPIENT_HASH_TAG))
+    .update(Address.fromString(contractId).toScVal().toXDR())
+    .update(escrowId)
+    .update(Address.fromString(recipient).toScVal().toXDR())
+    .update(claimSecret)
+    .digest();
+}
+
 /**
  * Build unsigned XDR for create_envelope
  */
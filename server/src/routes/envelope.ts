@@ -9,6 +9,7 @@ import { makeOpenLink, verifyOpenToken, generatePreimage } from '../security/lin
 import {
   newId,
   sha256Hex,
+  scopedEscrowId,
   buildCreateXDR,
   buildClaimTx,
   buildCancelXDR,
@@ -58,6 +59,13 @@ const CancelEnvelopeSchema = z.object({
   sender: z.string(),
 });
 
+const RefundSchema = z.object({
+  reason: z.number().int().min(0).max(0xffffffff).optional(), // u32 reason code
+});
+
+// Reason code recorded with admin-forced refunds when the caller gives none
+const ADMIN_REFUND_REASON_MANUAL = 1;
+
 /**
  * POST /api/envelope/create
  * Create a new envelope
@@ -457,6 +465,8 @@ router.get('/:id', async (req: Request, res: Response) => {
 /**
  * POST /api/envelope/:id/refund
  * Manually trigger refund for an envelope (edge cases)
+ * Before expiry the refund is queued behind the contract's refund delay and
+ * executed later by the outbox worker
  */
 router.post('/:id/refund', async (req: Request, res: Response) => {
   try {
@@ -467,6 +477,12 @@ router.post('/:id/refund', async (req: Request, res: Response) => {
       return res.status(400).json({ error: 'Invalid envelope ID format' });
     }
     
+    const body = RefundSchema.safeParse(req.body ?? {});
+    if (!body.success) {
+      return res.status(400).json({ error: 'Invalid refund reason' });
+    }
+    const refundReason = body.data.reason;
+    
     // Get envelope from database
     const envelope = await envelopeRepo.getEnvelope(id);
     
@@ -504,18 +520,19 @@ router.post('/:id/refund', async (req: Request, res: Response) => {
     }
     
     // Import Stellar SDK functions at top of handler
-    const { Keypair, TransactionBuilder, Operation, Address, nativeToScVal, BASE_FEE, Networks } = await import('@stellar/stellar-sdk');
-    const { Server: SorobanServer } = await import('@stellar/stellar-sdk/rpc');
-    const crypto = await import('crypto');
+    const { Keypair, TransactionBuilder, Operation, Address, nativeToScVal, scValToNative, BASE_FEE, Networks } = await import('@stellar/stellar-sdk');
+    const { Server: SorobanServer, Api } = await import('@stellar/stellar-sdk/rpc');
     
     const sorobanServer = new SorobanServer(process.env.SOROBAN_RPC_URL || 'https://soroban-testnet.stellar.org');
     const fundingKeypair = Keypair.fromSecret(FUNDING_SECRET_KEY);
     const fundingAccount = await sorobanServer.getAccount(fundingKeypair.publicKey());
     
-    // Generate escrow ID from envelope ID
-    const escrowId = crypto.createHash('sha256').update(id).digest();
+    // The contract stores the escrow under an id scoped to its sender
+    const escrowId = scopedEscrowId(envelope.sender, id);
     
-    // Build refund transaction
+    // Expired escrows refund directly; otherwise the admin queues a forced refund
+    // that only executes after the contract's refund delay
+    const reason = refundReason ?? ADMIN_REFUND_REASON_MANUAL;
     const tx = new TransactionBuilder(fundingAccount, {
       fee: BASE_FEE,
       networkPassphrase: process.env.NETWORK_PASSPHRASE || 'Test SDF Network ; September 2015',
@@ -524,9 +541,12 @@ router.post('/:id/refund', async (req: Request, res: Response) => {
         Operation.invokeContractFunction({
           contract: ESCROW_CONTRACT_ID,
           function: isExpired ? 'refund' : 'admin_refund',
-          args: [
-            nativeToScVal(escrowId, { type: 'bytes' }), // escrow_id
-          ],
+          args: isExpired
+            ? [nativeToScVal(escrowId, { type: 'bytes' })] // escrow_id
+            : [
+                nativeToScVal(escrowId, { type: 'bytes' }), // escrow_id
+                nativeToScVal(reason, { type: 'u32' }), // reason
+              ],
         })
       )
       .setTimeout(180)
@@ -549,7 +569,53 @@ router.post('/:id/refund', async (req: Request, res: Response) => {
       retries++;
     }
     
-    if (getTransactionResponse.status === 'SUCCESS') {
+    if (getTransactionResponse.status === 'SUCCESS' && !isExpired) {
+      // Read back when the queued refund becomes executable
+      const queryTx = new TransactionBuilder(await sorobanServer.getAccount(fundingKeypair.publicKey()), {
+        fee: BASE_FEE,
+        networkPassphrase: process.env.NETWORK_PASSPHRASE || 'Test SDF Network ; September 2015',
+      })
+        .addOperation(
+          Operation.invokeContractFunction({
+            contract: ESCROW_CONTRACT_ID,
+            function: 'get_queued_refund',
+            args: [nativeToScVal(escrowId, { type: 'bytes' })],
+          })
+        )
+        .setTimeout(30)
+        .build();
+      const sim = await sorobanServer.simulateTransaction(queryTx);
+      const queued = !Api.isSimulationError(sim) && sim.result?.retval
+        ? scValToNative(sim.result.retval)
+        : null;
+      if (!queued) {
+        throw new Error('Queued refund not found after admin_refund');
+      }
+      const executableAt = new Date(Number(queued.executable_at) * 1000);
+      
+      // The envelope stays FUNDED until the refund executes; the recipient can
+      // still claim during the delay
+      await prisma.outbox.create({
+        data: {
+          type: 'ESCROW_EXECUTE_REFUND',
+          payload: {
+            envelopeId: id,
+            escrowId: escrowId.toString('hex'),
+            reason,
+          },
+          runAfter: executableAt,
+        },
+      });
+      
+      return res.json({
+        ok: true,
+        message: 'Refund queued',
+        txHash: submitResult.hash,
+        envelopeId: id,
+        status: 'FUNDED',
+        refundExecutableAt: executableAt.toISOString(),
+      });
+    } else if (getTransactionResponse.status === 'SUCCESS') {
       // Update envelope status
       await prisma.envelope.update({
         where: { id },
@@ -143,7 +143,6 @@ export class GiftService {
           type: 'ESCROW_FUND',
           payload: {
             senderWalletId: input.senderWalletId,
-            recipientHash: hash,
             amountAtomic: input.amountAtomic,
             assetCode: input.assetCode,
             expiryTs: input.expiryTs,
@@ -257,7 +256,6 @@ export class GiftService {
         type: 'ESCROW_FUND',
         payload: {
           senderWalletId: input.senderWalletId,
-          recipientHash: hash,
           amountAtomic: perRecipientAmount.toString(),
           assetCode: input.assetCode,
           expiryTs: input.expiryTs,
@@ -384,7 +382,6 @@ export class GiftService {
           type: 'ESCROW_FUND',
           payload: {
             senderWalletId: input.senderWalletId,
-            recipientHash: hash,
             amountAtomic: perClaimAmount.toString(),
             assetCode: input.assetCode,
             expiryTs: input.expiryTs,